@@ -2,7 +2,7 @@ use std::env::current_dir;
 use std::fs::create_dir_all;
 
 use cosmwasm_schema::{export_schema, remove_schemas, schema_for};
-use cw20_merkle_airdrop::msg::{AlreadyClaimedResponse, ConfigResponse, ExecuteMsg, InstantiateMsg, IsClaimedResponse, LatestStageResponse, MerkleRootResponse, QueryMsg};
+use cw20_merkle_airdrop::msg::{AlreadyClaimedResponse, ClaimHookExecuteMsg, ConfigResponse, ExcludedResponse, ExecuteMsg, InstantiateMsg, IsClaimedResponse, LatestStageResponse, ListClaimsResponse, ListStagesResponse, MerkleRootResponse, QueryMsg, ReceiveMsg, RoleMembersResponse, RolesResponse, StageStatsResponse, VerifyClaimResponse};
 
 fn main() {
     let mut out_dir = current_dir().unwrap();
@@ -18,4 +18,13 @@ fn main() {
     export_schema(&schema_for!(IsClaimedResponse), &out_dir);
     export_schema(&schema_for!(ConfigResponse), &out_dir);
     export_schema(&schema_for!(AlreadyClaimedResponse), &out_dir);
+    export_schema(&schema_for!(ReceiveMsg), &out_dir);
+    export_schema(&schema_for!(StageStatsResponse), &out_dir);
+    export_schema(&schema_for!(VerifyClaimResponse), &out_dir);
+    export_schema(&schema_for!(ListClaimsResponse), &out_dir);
+    export_schema(&schema_for!(ExcludedResponse), &out_dir);
+    export_schema(&schema_for!(ListStagesResponse), &out_dir);
+    export_schema(&schema_for!(ClaimHookExecuteMsg), &out_dir);
+    export_schema(&schema_for!(RolesResponse), &out_dir);
+    export_schema(&schema_for!(RoleMembersResponse), &out_dir);
 }
@@ -1,20 +1,40 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
-use cosmwasm_std::{attr, to_binary, Addr, Binary, Deps, DepsMut, Env, MessageInfo, Response, StdResult, Uint128, WasmMsg};
+use cosmwasm_std::{attr, coins, from_binary, to_binary, Addr, BankMsg, Binary, BlockInfo, CosmosMsg, Decimal, Deps, DepsMut, Env, MessageInfo, Order, Reply, Response, StdError, StdResult, Storage, SubMsg, Timestamp, Uint128, WasmMsg};
 use cw2::{get_contract_version, set_contract_version};
-use cw20::Cw20ExecuteMsg;
+use cw_storage_plus::{Bound, Map};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use cw20::{BalanceResponse, Cw20ExecuteMsg, Cw20QueryMsg, Cw20ReceiveMsg};
 use sha2::Digest;
+use std::cmp::Ordering;
 use std::convert::TryInto;
 use cw_utils::{Expiration, Scheduled};
+use vote_hooks::VoteHookMsg;
+use klmd_custom_vesting::msg::{
+    QueryMsg as VestingQueryMsg, ReceiveMsg as VestingReceiveMsg, TokenAddressResponse,
+};
 
 use crate::error::ContractError;
-use crate::msg::{AlreadyClaimedResponse, ConfigResponse, ExecuteMsg, InstantiateMsg, IsClaimedResponse, LatestStageResponse, MerkleRootResponse, MigrateMsg, QueryMsg};
-use crate::state::{Config, CLAIM, CONFIG, LATEST_STAGE, MERKLE_ROOT, ALREADY_CLAIMED, STAGE_EXPIRATION, STAGE_START};
+use crate::msg::{AlreadyClaimedResponse, ClaimHookExecuteMsg, ClaimHookMsg, ClaimResponse, ConfigResponse, DecayTemplate, ExcludedResponse, ExecuteMsg, InstantiateMsg, IsClaimedResponse, LatestStageResponse, ListClaimsResponse, ListStagesResponse, MerkleRootResponse, MigrateMsg, QueryMsg, ReceiveMsg, RoleMembersResponse, RolesResponse, SignatureInfo, StageClaim, StageResponse, StageStatsResponse, StageStatus, TranchesTemplate, VerifyClaimResponse, VestingTemplate};
+use crate::signature::{tree_external_address, verify_external_address};
+use crate::state::{
+    ClaimRecord, Config, CrossChain, Role, StageDecay, StageMetadata, StageTranches, StageVesting, ALREADY_CLAIMED, ALREADY_CLAIMED_PREFIX, CLAIM, CLAIMED_BITS, CLAIM_HOOKS, CLAIM_HOOK_REPLIES,
+    EXCLUDED, PENDING_OWNER, ROLES,
+    CONFIG, LEGACY_CLAIM_PREFIX, LATEST_STAGE, MERKLE_ROOT, MERKLE_ROOT_PREFIX, STAGE_AMOUNT, STAGE_AMOUNT_KEY,
+    STAGE_CLOSED, STAGE_CLOSED_KEY, STAGE_CROSS_CHAIN, STAGE_CROSS_CHAIN_KEY, STAGE_DECAY, STAGE_TOKEN, STAGE_EXPIRATION,
+    STAGE_EXPIRATION_KEY, STAGE_INDEXED, STAGE_METADATA, STAGE_FUNDED, STAGE_FUNDED_KEY, STAGE_NATIVE_DENOM,
+    STAGE_NATIVE_DENOM_KEY, STAGE_PAUSED, STAGE_PAUSED_KEY, STAGE_START, STAGE_START_KEY,
+    STAGE_TRANCHES, STAGE_VESTING, STAGE_VESTING_KEY, TRANCHES_PAID, VOTES, VOTE_HEIGHTS, VOTE_MODULES,
+};
 
 // Version info, for migration info
 const CONTRACT_NAME: &str = "crates.io:cw20-merkle-airdrop";
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
+const MAX_LIMIT: u32 = 30;
+const DEFAULT_LIMIT: u32 = 10;
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
     deps: DepsMut,
@@ -49,14 +69,82 @@ pub fn execute(
 ) -> Result<Response, ContractError> {
     match msg {
         ExecuteMsg::UpdateConfig { new_owner } => execute_update_config(deps, env, info, new_owner),
-        ExecuteMsg::RegisterMerkleRoot { merkle_root, expiration, start } => {
-            execute_register_merkle_root(deps, env, info, merkle_root, expiration, start)
+        ExecuteMsg::AcceptOwnership {} => execute_accept_ownership(deps, env, info),
+        ExecuteMsg::GrantRole { role, address } => {
+            execute_set_role(deps, env, info, role, address, true)
+        }
+        ExecuteMsg::RevokeRole { role, address } => {
+            execute_set_role(deps, env, info, role, address, false)
         }
+        ExecuteMsg::RegisterMerkleRoot {
+            merkle_root,
+            expiration,
+            start,
+            native_denom,
+            cw20_token_address,
+            total_amount,
+            vesting,
+            cross_chain,
+            decay,
+            tranches,
+            indexed_leaves,
+            metadata,
+        } => execute_register_merkle_root(
+            deps,
+            env,
+            info,
+            merkle_root,
+            expiration,
+            start,
+            native_denom,
+            cw20_token_address,
+            total_amount,
+            vesting,
+            cross_chain,
+            decay,
+            tranches,
+            indexed_leaves,
+            metadata,
+        ),
+        ExecuteMsg::Receive(msg) => execute_receive(deps, env, info, msg),
         ExecuteMsg::Claim {
             stage,
             amount,
             proof,
-        } => execute_claim(deps, env, info, stage, amount, proof),
+            sig_info,
+            leaf_index,
+        } => execute_claim(deps, env, info, stage, amount, proof, sig_info, leaf_index),
+        ExecuteMsg::ClaimMany {
+            claims,
+            skip_claimed,
+        } => execute_claim_many(deps, env, info, claims, skip_claimed),
+        ExecuteMsg::PauseStage { stage } => execute_set_stage_paused(deps, env, info, stage, true),
+        ExecuteMsg::ResumeStage { stage } => {
+            execute_set_stage_paused(deps, env, info, stage, false)
+        }
+        ExecuteMsg::WithdrawUnclaimed {
+            stage,
+            recipient,
+            amount,
+        } => execute_withdraw_unclaimed(deps, env, info, stage, recipient, amount),
+        ExecuteMsg::BurnUnclaimed { stage, amount } => {
+            execute_burn_unclaimed(deps, env, info, stage, amount)
+        }
+        ExecuteMsg::UpdateStage {
+            stage,
+            expiration,
+            start,
+        } => execute_update_stage(deps, env, info, stage, expiration, start),
+        ExecuteMsg::AddClaimHook { address } => execute_add_claim_hook(deps, env, info, address),
+        ExecuteMsg::RemoveClaimHook { address } => {
+            execute_remove_claim_hook(deps, env, info, address)
+        }
+        ExecuteMsg::VoteHook(msg) => execute_vote_hook(deps, env, info, msg),
+        ExecuteMsg::Exclude {
+            stage,
+            addresses,
+            leaf_indexes,
+        } => execute_exclude(deps, env, info, stage, addresses, leaf_indexes),
     }
 }
 
@@ -73,40 +161,188 @@ pub fn execute_update_config(
         return Err(ContractError::Unauthorized {});
     }
 
-    // if owner some validated to addr, otherwise set to none
-    let mut tmp_owner = None;
-    if let Some(addr) = new_owner {
-        tmp_owner = Some(deps.api.addr_validate(&addr)?)
+    // a new owner takes over once accepting, none freezes the contract right away
+    let mut attrs = vec![attr("action", "update_config")];
+    match new_owner {
+        Some(addr) => {
+            let pending_owner = deps.api.addr_validate(&addr)?;
+            PENDING_OWNER.save(deps.storage, &pending_owner)?;
+            attrs.push(attr("pending_owner", pending_owner));
+        }
+        None => {
+            PENDING_OWNER.remove(deps.storage);
+            CONFIG.update(deps.storage, |mut exists| -> StdResult<_> {
+                exists.owner = None;
+                Ok(exists)
+            })?;
+        }
+    }
+
+    Ok(Response::new().add_attributes(attrs))
+}
+
+pub fn execute_accept_ownership(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let pending_owner = PENDING_OWNER
+        .may_load(deps.storage)?
+        .ok_or(ContractError::Unauthorized {})?;
+    if info.sender != pending_owner {
+        return Err(ContractError::Unauthorized {});
     }
 
+    PENDING_OWNER.remove(deps.storage);
     CONFIG.update(deps.storage, |mut exists| -> StdResult<_> {
-        exists.owner = tmp_owner;
+        exists.owner = Some(pending_owner);
         Ok(exists)
     })?;
 
-    Ok(Response::new().add_attribute("action", "update_config"))
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "accept_ownership"),
+        attr("owner", info.sender),
+    ]))
 }
 
-pub fn execute_register_merkle_root(
+pub fn execute_set_role(
     deps: DepsMut,
     _env: Env,
     info: MessageInfo,
-    merkle_root: String,
-    expiration: Option<Expiration>,
-    start: Option<Scheduled>,
+    role: Role,
+    address: String,
+    granted: bool,
 ) -> Result<Response, ContractError> {
+    // authorize owner
     let cfg = CONFIG.load(deps.storage)?;
-
-    // if owner set validate, otherwise unauthorized
     let owner = cfg.owner.ok_or(ContractError::Unauthorized {})?;
     if info.sender != owner {
         return Err(ContractError::Unauthorized {});
     }
 
+    let address = deps.api.addr_validate(&address)?;
+    if granted {
+        ROLES.save(deps.storage, (role.key(), &address), &true)?;
+    } else {
+        ROLES.remove(deps.storage, (role.key(), &address));
+    }
+
+    let action = if granted { "grant_role" } else { "revoke_role" };
+    Ok(Response::new().add_attributes(vec![
+        attr("action", action),
+        attr("role", role.key()),
+        attr("address", address),
+    ]))
+}
+
+/// Checks that the sender is the owner or was granted the role. Roles are void once the
+/// contract is frozen.
+fn authorize(storage: &dyn Storage, sender: &Addr, role: Role) -> Result<(), ContractError> {
+    let cfg = CONFIG.load(storage)?;
+    let owner = cfg.owner.ok_or(ContractError::Unauthorized {})?;
+    if *sender != owner && !ROLES.has(storage, (role.key(), sender)) {
+        return Err(ContractError::Unauthorized {});
+    }
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn execute_register_merkle_root(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    merkle_root: String,
+    expiration: Option<Expiration>,
+    start: Option<Scheduled>,
+    native_denom: Option<String>,
+    cw20_token_address: Option<String>,
+    total_amount: Option<Uint128>,
+    vesting: Option<VestingTemplate>,
+    cross_chain: Option<CrossChain>,
+    decay: Option<DecayTemplate>,
+    tranches: Option<TranchesTemplate>,
+    indexed_leaves: Option<bool>,
+    metadata: Option<StageMetadata>,
+) -> Result<Response, ContractError> {
+    authorize(deps.storage, &info.sender, Role::Registrar)?;
+    // the decay treasury receives funds, picking it takes the right to withdraw them
+    if decay.is_some() {
+        authorize(deps.storage, &info.sender, Role::Withdrawer)?;
+    }
+    let cfg = CONFIG.load(deps.storage)?;
+
     // check merkle root length
     let mut root_buf: [u8; 32] = [0; 32];
     hex::decode_to_slice(merkle_root.to_string(), &mut root_buf)?;
 
+    if total_amount == Some(Uint128::zero()) {
+        return Err(ContractError::InvalidInput {});
+    }
+
+    // stages pay out in either a native denom or a cw20 token, the config one by default
+    let cw20_token_address = match (&native_denom, cw20_token_address) {
+        (Some(_), Some(_)) => return Err(ContractError::InvalidInput {}),
+        (_, token) => token.map(|t| deps.api.addr_validate(&t)).transpose()?,
+    };
+    let stage_cw20 = cw20_token_address.as_ref().unwrap_or(&cfg.cw20_token_address);
+
+    // native stages must be backed up front by the part of the contract balance not owed to
+    // the other open stages of the denom
+    if let Some(denom) = &native_denom {
+        let total_amount = total_amount.ok_or(ContractError::MissingTotalAmount {})?;
+        if denom.is_empty() {
+            return Err(ContractError::InvalidInput {});
+        }
+        let balance = deps
+            .querier
+            .query_balance(&env.contract.address, denom)?
+            .amount;
+        let token = StageToken::Native(denom.clone());
+        let required = token_owed(deps.as_ref(), &cfg, &token)? + total_amount;
+        if balance < required {
+            return Err(ContractError::InsufficientFunds {
+                denom: denom.clone(),
+                balance,
+                required,
+            });
+        }
+    }
+
+    // vesting contract must vest the stage token
+    let vesting = vesting
+        .map(|v| validate_vesting(deps.as_ref(), stage_cw20, &native_denom, v))
+        .transpose()?;
+
+    if let Some(CrossChain::Cosmos { hrp }) = &cross_chain {
+        if hrp.is_empty() {
+            return Err(ContractError::InvalidInput {});
+        }
+    }
+
+    // decay runs from the stage start to its expiration
+    let decay = decay
+        .map(|d| validate_decay(deps.as_ref(), &start, &expiration, d))
+        .transpose()?;
+
+    // vested claims are not split into tranches
+    let tranches = tranches
+        .map(|t| validate_tranches(deps.as_ref(), &env, &vesting, t))
+        .transpose()?;
+
+    // tranches are tracked per address
+    let indexed_leaves = indexed_leaves.unwrap_or(false);
+    if indexed_leaves && tranches.is_some() {
+        return Err(ContractError::InvalidInput {});
+    }
+
+    if let Some(metadata) = &metadata {
+        let empty = |field: &Option<String>| field.as_ref().map_or(false, String::is_empty);
+        if empty(&metadata.title) || empty(&metadata.description) || metadata.total_leaves == Some(0)
+        {
+            return Err(ContractError::InvalidInput {});
+        }
+    }
+
     let stage = LATEST_STAGE.update(deps.storage, |stage| -> StdResult<_> { Ok(stage + 1) })?;
 
     MERKLE_ROOT.save(deps.storage, stage, &merkle_root)?;
@@ -121,46 +357,626 @@ pub fn execute_register_merkle_root(
         STAGE_START.save(deps.storage, stage, &start)?;
     }
 
-    Ok(Response::new().add_attributes(vec![
+    // save payout denom and total, native stages are funded by the balance checked above. Stages
+    // without a total are not tracked, as before totals existed.
+    if let Some(total_amount) = total_amount {
+        STAGE_AMOUNT.save(deps.storage, stage, &total_amount)?;
+        if let Some(denom) = &native_denom {
+            STAGE_NATIVE_DENOM.save(deps.storage, stage, denom)?;
+            STAGE_FUNDED.save(deps.storage, stage, &total_amount)?;
+        }
+    }
+    if let Some(token) = &cw20_token_address {
+        STAGE_TOKEN.save(deps.storage, stage, token)?;
+    }
+    if let Some(vesting) = &vesting {
+        STAGE_VESTING.save(deps.storage, stage, vesting)?;
+    }
+    if let Some(cross_chain) = &cross_chain {
+        STAGE_CROSS_CHAIN.save(deps.storage, stage, cross_chain)?;
+    }
+    if let Some(decay) = &decay {
+        STAGE_DECAY.save(deps.storage, stage, decay)?;
+    }
+    if let Some(tranches) = &tranches {
+        STAGE_TRANCHES.save(deps.storage, stage, tranches)?;
+        VOTE_MODULES.save(deps.storage, &tranches.proposal_module, &true)?;
+    }
+    if indexed_leaves {
+        STAGE_INDEXED.save(deps.storage, stage, &true)?;
+    }
+    if let Some(metadata) = &metadata {
+        STAGE_METADATA.save(deps.storage, stage, metadata)?;
+    }
+
+    let mut attrs = vec![
         attr("action", "register_merkle_root"),
         attr("stage", stage.to_string()),
         attr("merkle_root", merkle_root),
+    ];
+    if let Some(total_amount) = total_amount {
+        attrs.push(attr("total_amount", total_amount));
+    }
+    if let Some(denom) = native_denom {
+        attrs.push(attr("native_denom", denom));
+    }
+    if let Some(token) = cw20_token_address {
+        attrs.push(attr("cw20_token_address", token));
+    }
+    if let Some(vesting) = vesting {
+        attrs.push(attr("vesting_contract", vesting.vesting_contract));
+    }
+    if let Some(tranches) = tranches {
+        attrs.push(attr("proposal_module", tranches.proposal_module));
+    }
+
+    Ok(Response::new().add_attributes(attrs))
+}
+
+/// Returns the amount the open stages paid out in `token` still owe to claimants.
+fn token_owed(deps: Deps, config: &Config, token: &StageToken) -> StdResult<Uint128> {
+    let stages: Vec<u32> = STAGE_EXPIRATION
+        .keys(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<_>>()?;
+
+    let mut owed = Uint128::zero();
+    for stage in stages {
+        if STAGE_CLOSED.may_load(deps.storage, stage)?.unwrap_or(false)
+            || stage_token(deps, config, stage)? != *token
+        {
+            continue;
+        }
+        let funded = STAGE_FUNDED.may_load(deps.storage, stage)?.unwrap_or_default();
+        let already_claimed = ALREADY_CLAIMED.may_load(deps.storage, stage)?.unwrap_or_default();
+        owed += funded.saturating_sub(already_claimed);
+    }
+    Ok(owed)
+}
+
+fn validate_vesting(
+    deps: Deps,
+    cw20_token_address: &Addr,
+    native_denom: &Option<String>,
+    vesting: VestingTemplate,
+) -> Result<StageVesting, ContractError> {
+    if native_denom.is_some()
+        || vesting.liquid_percent > Decimal::one()
+        || vesting.cliff > vesting.duration
+    {
+        return Err(ContractError::InvalidInput {});
+    }
+    let vesting_contract = deps.api.addr_validate(&vesting.vesting_contract)?;
+    let resp: TokenAddressResponse = deps
+        .querier
+        .query_wasm_smart(&vesting_contract, &VestingQueryMsg::TokenAddress {})?;
+    if resp.token_address != *cw20_token_address {
+        return Err(ContractError::InvalidInput {});
+    }
+    Ok(StageVesting {
+        vesting_contract,
+        cliff: vesting.cliff,
+        duration: vesting.duration,
+        liquid_percent: vesting.liquid_percent,
+    })
+}
+
+/// Returns whether `start` comes strictly before `expiration`. Starts and expirations of different
+/// kinds can't be compared, the expiration then only has to be ahead.
+fn starts_before(start: &Scheduled, expiration: &Expiration, block: &BlockInfo) -> bool {
+    match (start, expiration) {
+        (_, Expiration::Never {}) => true,
+        (Scheduled::AtHeight(start), Expiration::AtHeight(end)) => start < end,
+        (Scheduled::AtTime(start), Expiration::AtTime(end)) => start < end,
+        _ => !expiration.is_expired(block),
+    }
+}
+
+fn validate_decay(
+    deps: Deps,
+    start: &Option<Scheduled>,
+    expiration: &Option<Expiration>,
+    decay: DecayTemplate,
+) -> Result<StageDecay, ContractError> {
+    let (start, expiration) = match (start, expiration) {
+        (Some(start), Some(expiration)) => (start, expiration),
+        _ => return Err(ContractError::InvalidInput {}),
+    };
+    if stage_progress(start, expiration, None).is_none() || decay.floor > Decimal::one() {
+        return Err(ContractError::InvalidInput {});
+    }
+    Ok(StageDecay {
+        floor: decay.floor,
+        treasury: deps.api.addr_validate(&decay.treasury)?,
+    })
+}
+
+fn validate_tranches(
+    deps: Deps,
+    env: &Env,
+    vesting: &Option<StageVesting>,
+    tranches: TranchesTemplate,
+) -> Result<StageTranches, ContractError> {
+    let total = tranches
+        .tranches
+        .iter()
+        .fold(Decimal::zero(), |sum, part| sum + *part);
+    if vesting.is_some()
+        || tranches.tranches.len() < 2
+        || tranches.tranches.iter().any(Decimal::is_zero)
+        || total != Decimal::one()
+    {
+        return Err(ContractError::InvalidInput {});
+    }
+    Ok(StageTranches {
+        proposal_module: deps.api.addr_validate(&tranches.proposal_module)?,
+        tranches: tranches.tranches,
+        since_height: env.block.height,
+    })
+}
+
+/// Returns the elapsed and the total duration from start to expiration, in blocks or
+/// nanoseconds. None if they are not both heights or both times, in order.
+fn stage_progress(
+    start: &Scheduled,
+    expiration: &Expiration,
+    block: Option<&BlockInfo>,
+) -> Option<(u64, u64)> {
+    let (now, start, end) = match (start, expiration) {
+        (Scheduled::AtHeight(start), Expiration::AtHeight(end)) => {
+            (block.map(|b| b.height), *start, *end)
+        }
+        (Scheduled::AtTime(start), Expiration::AtTime(end)) => (
+            block.map(|b| b.time.nanos()),
+            start.nanos(),
+            end.nanos(),
+        ),
+        _ => return None,
+    };
+    if end <= start {
+        return None;
+    }
+    let elapsed = now.map_or(0, |now| now.clamp(start, end) - start);
+    Some((elapsed, end - start))
+}
+
+/// Returns the part of a claimed amount that is paid out to the claimer. On decaying stages it
+/// goes down linearly from the full amount at the stage start to the floor at its expiration.
+fn claimable_amount(
+    storage: &dyn Storage,
+    block: &BlockInfo,
+    stage: u32,
+    amount: Uint128,
+) -> StdResult<Uint128> {
+    let decay = match STAGE_DECAY.may_load(storage, stage)? {
+        Some(decay) => decay,
+        None => return Ok(amount),
+    };
+    let start = STAGE_START.load(storage, stage)?;
+    let expiration = STAGE_EXPIRATION.load(storage, stage)?;
+    // checked when the stage was registered
+    let (elapsed, duration) = stage_progress(&start, &expiration, Some(block)).unwrap_or((0, 1));
+
+    let forfeitable = amount - amount * decay.floor;
+    Ok(amount - forfeitable.multiply_ratio(elapsed, duration))
+}
+
+pub fn execute_receive(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    wrapper: Cw20ReceiveMsg,
+) -> Result<Response, ContractError> {
+    match from_binary(&wrapper.msg)? {
+        ReceiveMsg::FundStage { stage } => execute_fund_stage(deps, info, stage, wrapper.amount),
+    }
+}
+
+pub fn execute_fund_stage(
+    deps: DepsMut,
+    info: MessageInfo,
+    stage: u32,
+    amount: Uint128,
+) -> Result<Response, ContractError> {
+    // only the stage's cw20 token can fund it
+    let config = CONFIG.load(deps.storage)?;
+    if stage_token(deps.as_ref(), &config, stage)? != StageToken::Cw20(info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+    if STAGE_CLOSED.may_load(deps.storage, stage)?.unwrap_or(false) {
+        return Err(ContractError::StageClosed { stage });
+    }
+
+    let total_amount = STAGE_AMOUNT
+        .may_load(deps.storage, stage)?
+        .ok_or(ContractError::MissingTotalAmount {})?;
+    let funded = STAGE_FUNDED.may_load(deps.storage, stage)?.unwrap_or_default() + amount;
+    if funded > total_amount {
+        return Err(ContractError::StageOverfunded { stage });
+    }
+    STAGE_FUNDED.save(deps.storage, stage, &funded)?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "fund_stage"),
+        attr("stage", stage.to_string()),
+        attr("amount", amount),
+        attr("funded_amount", funded),
     ]))
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn execute_claim(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     info: MessageInfo,
-    stage: u8,
+    stage: u32,
     amount: Uint128,
     proof: Vec<String>,
+    sig_info: Option<SignatureInfo>,
+    leaf_index: Option<u64>,
 ) -> Result<Response, ContractError> {
+    let claimant = claimant(deps.as_ref(), stage, &info.sender, &sig_info)?;
+    let due = process_claim(
+        deps.storage,
+        &env,
+        &claimant,
+        &info.sender,
+        stage,
+        amount,
+        proof,
+        leaf_index,
+    )?;
+
+    let config = CONFIG.load(deps.storage)?;
+    let claimable = claimable_amount(deps.storage, &env.block, stage, due)?;
+    let mut msgs = claim_msgs(deps.as_ref(), &env, &config, stage, &info.sender, claimable)?;
+    let mut attrs = vec![
+        attr("action", "claim"),
+        attr("stage", stage.to_string()),
+        attr("address", info.sender.clone()),
+        attr("amount", claimable),
+    ];
+    if claimant != info.sender {
+        attrs.push(attr("external_address", claimant));
+    }
+    let forfeited = due - claimable;
+    if !forfeited.is_zero() {
+        msgs.push(forfeit_msg(deps.as_ref(), &config, stage, forfeited)?);
+        attrs.push(attr("forfeited", forfeited));
+    }
+    let hooks = claim_hooks(deps.storage, stage, &info.sender, claimable)?;
+    let res = Response::new()
+        .add_messages(msgs)
+        .add_submessages(hooks)
+        .add_attributes(attrs);
+    Ok(res)
+}
+
+pub fn execute_claim_many(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    claims: Vec<StageClaim>,
+    skip_claimed: bool,
+) -> Result<Response, ContractError> {
+    if claims.is_empty() {
+        return Err(ContractError::InvalidInput {});
+    }
+
+    let config = CONFIG.load(deps.storage)?;
+    let mut msgs = vec![];
+    // direct payouts are summed up into one transfer per token
+    let mut payouts: Vec<(StageToken, Uint128)> = vec![];
+    let mut stages = vec![];
+    let mut hooks = vec![];
+    let mut total = Uint128::zero();
+    for StageClaim {
+        stage,
+        amount,
+        proof,
+        sig_info,
+        leaf_index,
+    } in claims
+    {
+        let claimant = claimant(deps.as_ref(), stage, &info.sender, &sig_info)?;
+        let tranched = STAGE_TRANCHES.has(deps.storage, stage);
+        if skip_claimed && !tranched && is_claimed(deps.storage, stage, &claimant, leaf_index)? {
+            continue;
+        }
+        let due = match process_claim(
+            deps.storage,
+            &env,
+            &claimant,
+            &info.sender,
+            stage,
+            amount,
+            proof,
+            leaf_index,
+        ) {
+            // nothing more unlocked on tranche stages
+            Err(ContractError::Claimed {}) | Err(ContractError::TrancheLocked { .. })
+                if skip_claimed && tranched =>
+            {
+                continue
+            }
+            res => res?,
+        };
+
+        let claimable = claimable_amount(deps.storage, &env.block, stage, due)?;
+        if STAGE_VESTING.has(deps.storage, stage) {
+            msgs.extend(claim_msgs(deps.as_ref(), &env, &config, stage, &info.sender, claimable)?);
+        } else {
+            let token = stage_token(deps.as_ref(), &config, stage)?;
+            match payouts.iter_mut().find(|(t, _)| *t == token) {
+                Some((_, sum)) => *sum += claimable,
+                None => payouts.push((token, claimable)),
+            }
+        }
+        if claimable < due {
+            msgs.push(forfeit_msg(deps.as_ref(), &config, stage, due - claimable)?);
+        }
+        hooks.extend(claim_hooks(deps.storage, stage, &info.sender, claimable)?);
+        stages.push(stage.to_string());
+        total += claimable;
+    }
+    for (token, amount) in payouts {
+        msgs.push(token.transfer_msg(&info.sender, amount)?);
+    }
+
+    Ok(Response::new()
+        .add_messages(msgs)
+        .add_submessages(hooks)
+        .add_attributes(vec![
+            attr("action", "claim_many"),
+            attr("stages", stages.join(",")),
+            attr("address", info.sender),
+            attr("amount", total),
+        ]))
+}
+
+/// Builds the `ClaimHook` messages of a claim. Each message replies on error with the hook's
+/// position as id, and `CLAIM_HOOK_REPLIES` maps that id to the hook's address so `reply` removes
+/// the hook that failed.
+fn claim_hooks(
+    storage: &mut dyn Storage,
+    stage: u32,
+    address: &Addr,
+    amount: Uint128,
+) -> StdResult<Vec<SubMsg>> {
+    let msg = to_binary(&ClaimHookExecuteMsg::ClaimHook(ClaimHookMsg {
+        stage,
+        address: address.to_string(),
+        amount,
+    }))?;
+    let mut hooks = vec![];
+    let sub_msgs = CLAIM_HOOKS.prepare_hooks(storage, |hook| {
+        let execute = WasmMsg::Execute {
+            contract_addr: hook.to_string(),
+            msg: msg.clone(),
+            funds: vec![],
+        };
+        let sub_msg = SubMsg::reply_on_error(execute, hooks.len() as u64);
+        hooks.push(hook);
+        Ok(sub_msg)
+    })?;
+    for (id, hook) in hooks.iter().enumerate() {
+        CLAIM_HOOK_REPLIES.save(storage, id as u64, hook)?;
+    }
+    Ok(sub_msgs)
+}
+
+/// Returns the address listed in the stage's merkle tree for the sender. On cross chain stages
+/// this is the external address whose signature of the sender is verified.
+fn claimant(
+    deps: Deps,
+    stage: u32,
+    sender: &Addr,
+    sig_info: &Option<SignatureInfo>,
+) -> Result<Addr, ContractError> {
+    match (STAGE_CROSS_CHAIN.may_load(deps.storage, stage)?, sig_info) {
+        (None, None) => Ok(sender.clone()),
+        (None, Some(_)) => Err(ContractError::InvalidInput {}),
+        (Some(_), None) => Err(ContractError::SignatureRequired { stage }),
+        (Some(chain), Some(sig_info)) => {
+            verify_external_address(deps.api, &chain, sig_info, sender)?;
+            Ok(Addr::unchecked(tree_external_address(&chain, &sig_info.external_address)))
+        }
+    }
+}
+
+/// Verifies a claim against the stage's merkle root and records it. Returns the amount due,
+/// paying it out is left to the caller. Votes of the recipient unlock tranches.
+#[allow(clippy::too_many_arguments)]
+fn process_claim(
+    storage: &mut dyn Storage,
+    env: &Env,
+    sender: &Addr,
+    recipient: &Addr,
+    stage: u32,
+    amount: Uint128,
+    proof: Vec<String>,
+    leaf_index: Option<u64>,
+) -> Result<Uint128, ContractError> {
     // airdrop begun
-    let start = STAGE_START.may_load(deps.storage, stage)?;
+    let start = STAGE_START.may_load(storage, stage)?;
     if let Some(start) = start {
-        if !start.is_triggered(&_env.block) {
+        if !start.is_triggered(&env.block) {
             return Err(ContractError::StageNotBegun {stage, start});
         }
     }
     // not expired
-    let expiration = STAGE_EXPIRATION.load(deps.storage, stage)?;
-    if expiration.is_expired(&_env.block) {
+    let expiration = STAGE_EXPIRATION.load(storage, stage)?;
+    if expiration.is_expired(&env.block) {
         return Err(ContractError::StageExpired {stage, expiration});
     }
+    // not closed
+    if STAGE_CLOSED.may_load(storage, stage)?.unwrap_or(false) {
+        return Err(ContractError::StageClosed { stage });
+    }
+    // not paused
+    if STAGE_PAUSED.may_load(storage, stage)?.unwrap_or(false) {
+        return Err(ContractError::StagePaused { stage });
+    }
+    // not excluded
+    if EXCLUDED.has(storage, (stage, sender)) {
+        return Err(ContractError::AddressExcluded {
+            stage,
+            address: sender.to_string(),
+        });
+    }
 
-    // verify not claimed
-    let claimed = CLAIM.may_load(deps.storage, (&info.sender, stage))?;
-    if claimed.is_some() {
+    // leaves of indexed stages commit to the index of their claimed flag
+    match (STAGE_INDEXED.may_load(storage, stage)?.unwrap_or(false), leaf_index) {
+        (true, None) => return Err(ContractError::LeafIndexRequired { stage }),
+        (false, Some(_)) => return Err(ContractError::InvalidInput {}),
+        _ => {}
+    }
+
+    // verify not claimed, tranche stages are claimed again for each unlocked tranche
+    let tranches = STAGE_TRANCHES.may_load(storage, stage)?;
+    if tranches.is_none() && is_claimed(storage, stage, sender, leaf_index)? {
         return Err(ContractError::Claimed {});
     }
 
-    let already_claimed = ALREADY_CLAIMED.may_load(deps.storage, stage.into())?.unwrap_or(Default::default());
+    if !verify_proof(storage, stage, sender, amount, proof, leaf_index)? {
+        return Err(ContractError::VerificationFailed {});
+    }
 
-    let config = CONFIG.load(deps.storage)?;
-    let merkle_root = MERKLE_ROOT.load(deps.storage, stage.into())?;
+    let due = match &tranches {
+        Some(tranches) => {
+            let (due, paid, unlocked) =
+                tranche_due(storage, tranches, stage, sender, recipient, amount)?;
+            if unlocked <= paid {
+                if paid as usize == tranches.tranches.len() {
+                    return Err(ContractError::Claimed {});
+                }
+                return Err(ContractError::TrancheLocked { stage });
+            }
+            TRANCHES_PAID.save(storage, (stage, sender), &unlocked)?;
+            due
+        }
+        None => amount,
+    };
+
+    let already_claimed = ALREADY_CLAIMED.may_load(storage, stage)?.unwrap_or_default();
+    let new_value_claimed = already_claimed + due;
+
+    // stages registered before totals were tracked are not capped
+    if let Some(total_amount) = STAGE_AMOUNT.may_load(storage, stage)? {
+        if new_value_claimed > total_amount {
+            return Err(ContractError::StageAmountExceeded { stage });
+        }
+        let funded = STAGE_FUNDED.may_load(storage, stage)?.unwrap_or_default();
+        if new_value_claimed > funded {
+            return Err(ContractError::StageUnderfunded { stage });
+        }
+    }
+
+    // Update claim index to the current stage, indexed stages only set their claimed bit
+    if let Some(index) = leaf_index {
+        CLAIMED_BITS.update(storage, (stage, index / 64), |word| -> StdResult<_> {
+            Ok(word.unwrap_or_default() | 1 << (index % 64))
+        })?;
+    } else {
+        let claimed = CLAIM
+            .may_load(storage, (stage, sender))?
+            .map_or_else(Uint128::zero, |c| c.amount);
+        let claim = ClaimRecord {
+            amount: claimed + due,
+            height: env.block.height,
+            time: env.block.time,
+        };
+        CLAIM.save(storage, (stage, sender), &claim)?;
+    }
+
+    ALREADY_CLAIMED.save(storage, stage, &new_value_claimed)?;
+
+    Ok(due)
+}
+
+/// Returns whether the address claimed the stage. Indexed stages look the leaf index up in
+/// their claimed bitmap instead and require it.
+fn is_claimed(
+    storage: &dyn Storage,
+    stage: u32,
+    address: &Addr,
+    leaf_index: Option<u64>,
+) -> StdResult<bool> {
+    if leaf_index.is_none() && STAGE_INDEXED.may_load(storage, stage)?.unwrap_or(false) {
+        return Err(StdError::generic_err(
+            ContractError::LeafIndexRequired { stage }.to_string(),
+        ));
+    }
+    match leaf_index {
+        Some(index) => {
+            let word = CLAIMED_BITS
+                .may_load(storage, (stage, index / 64))?
+                .unwrap_or_default();
+            Ok(word & 1 << (index % 64) != 0)
+        }
+        None => Ok(CLAIM.has(storage, (stage, address))),
+    }
+}
+
+/// Returns the amount due for the tranches the recipient's votes unlocked, the number of
+/// tranches already paid and the number unlocked.
+fn tranche_due(
+    storage: &dyn Storage,
+    tranches: &StageTranches,
+    stage: u32,
+    claimant: &Addr,
+    recipient: &Addr,
+    amount: Uint128,
+) -> StdResult<(Uint128, u32, u32)> {
+    let paid = TRANCHES_PAID
+        .may_load(storage, (stage, claimant))?
+        .unwrap_or_default();
+    let votes = VOTE_HEIGHTS
+        .prefix((&tranches.proposal_module, recipient))
+        .range(
+            storage,
+            Some(Bound::exclusive(tranches.since_height)),
+            None,
+            Order::Ascending,
+        )
+        .map(|item| item.map(|(_, count)| count))
+        .sum::<StdResult<u64>>()?;
+    // the first tranche is unlocked by claiming, each vote since the registration unlocks one more
+    let unlocked = (votes + 1).min(tranches.tranches.len() as u64) as u32;
+
+    // amount of the first count tranches, the last one gets the rounding remainder
+    let amount_until = |count: u32| {
+        if count as usize == tranches.tranches.len() {
+            amount
+        } else {
+            tranches.tranches[..count as usize]
+                .iter()
+                .fold(Uint128::zero(), |sum, part| sum + amount * *part)
+        }
+    };
+    let due = if unlocked > paid {
+        amount_until(unlocked) - amount_until(paid)
+    } else {
+        Uint128::zero()
+    };
+    Ok((due, paid, unlocked))
+}
+
+/// Checks the proof of address and amount against the stage's merkle root. Leaves of indexed
+/// stages are prefixed with the leaf index, `index:address amount`.
+fn verify_proof(
+    storage: &dyn Storage,
+    stage: u32,
+    address: &Addr,
+    amount: Uint128,
+    proof: Vec<String>,
+    leaf_index: Option<u64>,
+) -> Result<bool, ContractError> {
+    let merkle_root = MERKLE_ROOT.load(storage, stage)?;
 
-    let user_input = format!("{}{}", info.sender, amount);
+    let user_input = match leaf_index {
+        Some(index) => format!("{}:{}{}", index, address, amount),
+        None => format!("{}{}", address, amount),
+    };
     let hash = sha2::Sha256::digest(user_input.as_bytes())
         .as_slice()
         .try_into()
@@ -179,44 +995,529 @@ pub fn execute_claim(
 
     let mut root_buf: [u8; 32] = [0; 32];
     hex::decode_to_slice(merkle_root, &mut root_buf)?;
-    if root_buf != hash {
-        return Err(ContractError::VerificationFailed {});
-    }
+    Ok(root_buf == hash)
+}
 
-    // Update claim index to the current stage
-    CLAIM.save(deps.storage, (&info.sender, stage.into()), &true)?;
+pub fn execute_set_stage_paused(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    stage: u32,
+    paused: bool,
+) -> Result<Response, ContractError> {
+    authorize(deps.storage, &info.sender, Role::Pauser)?;
 
-    let new_value_claimed = already_claimed + amount;
-    ALREADY_CLAIMED.save(deps.storage, stage.into(), &new_value_claimed)?;
+    // stage must exist
+    MERKLE_ROOT.load(deps.storage, stage)?;
+    STAGE_PAUSED.save(deps.storage, stage, &paused)?;
 
-    let res = Response::new()
-        .add_message(WasmMsg::Execute {
-            contract_addr: config.cw20_token_address.to_string(),
-            funds: vec![],
-            msg: to_binary(&Cw20ExecuteMsg::Transfer {
-                recipient: info.sender.to_string(),
-                amount,
-            })?,
-        })
-        .add_attributes(vec![
-            attr("action", "claim"),
-            attr("stage", stage.to_string()),
-            attr("address", info.sender),
-            attr("amount", amount),
-        ]);
-    Ok(res)
+    let action = if paused { "pause_stage" } else { "resume_stage" };
+    Ok(Response::new().add_attributes(vec![
+        attr("action", action),
+        attr("stage", stage.to_string()),
+    ]))
 }
 
-#[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+pub fn execute_update_stage(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    stage: u32,
+    expiration: Option<Expiration>,
+    start: Option<Scheduled>,
+) -> Result<Response, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+
+    // if owner set validate, otherwise unauthorized
+    let owner = cfg.owner.ok_or(ContractError::Unauthorized {})?;
+    if info.sender != owner {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    if expiration.is_none() && start.is_none() {
+        return Err(ContractError::InvalidInput {});
+    }
+    // stage must exist
+    MERKLE_ROOT.load(deps.storage, stage)?;
+    if STAGE_CLOSED.may_load(deps.storage, stage)?.unwrap_or(false) {
+        return Err(ContractError::StageClosed { stage });
+    }
+
+    let old_expiration = STAGE_EXPIRATION.load(deps.storage, stage)?;
+    let mut attrs = vec![
+        attr("action", "update_stage"),
+        attr("stage", stage.to_string()),
+    ];
+
+    // expiration can only be extended
+    if let Some(expiration) = expiration {
+        if expiration.partial_cmp(&old_expiration) != Some(Ordering::Greater) {
+            return Err(ContractError::InvalidInput {});
+        }
+        STAGE_EXPIRATION.save(deps.storage, stage, &expiration)?;
+        attrs.push(attr("old_expiration", old_expiration.to_string()));
+        attrs.push(attr("new_expiration", expiration.to_string()));
+    }
+
+    // start can only be moved before the stage begins, to a point still ahead and before the
+    // expiration
+    if let Some(start) = start {
+        let old_start = match STAGE_START.may_load(deps.storage, stage)? {
+            Some(old_start) if !old_start.is_triggered(&env.block) => old_start,
+            _ => return Err(ContractError::StageBegun { stage }),
+        };
+        let expiration = STAGE_EXPIRATION.load(deps.storage, stage)?;
+        if start.is_triggered(&env.block) || !starts_before(&start, &expiration, &env.block) {
+            return Err(ContractError::InvalidInput {});
+        }
+        STAGE_START.save(deps.storage, stage, &start)?;
+        attrs.push(attr("old_start", old_start.to_string()));
+        attrs.push(attr("new_start", start.to_string()));
+    }
+
+    // decay still needs a start before the expiration, of the same kind
+    if STAGE_DECAY.has(deps.storage, stage) {
+        let start = STAGE_START.load(deps.storage, stage)?;
+        let expiration = STAGE_EXPIRATION.load(deps.storage, stage)?;
+        if stage_progress(&start, &expiration, None).is_none() {
+            return Err(ContractError::InvalidInput {});
+        }
+    }
+
+    Ok(Response::new().add_attributes(attrs))
+}
+
+pub fn execute_exclude(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    stage: u32,
+    addresses: Vec<String>,
+    leaf_indexes: Option<Vec<u64>>,
+) -> Result<Response, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+
+    // if owner set validate, otherwise unauthorized
+    let owner = cfg.owner.ok_or(ContractError::Unauthorized {})?;
+    if info.sender != owner {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    if addresses.is_empty() {
+        return Err(ContractError::InvalidInput {});
+    }
+    // stage must exist
+    MERKLE_ROOT.load(deps.storage, stage)?;
+    if STAGE_CLOSED.may_load(deps.storage, stage)?.unwrap_or(false) {
+        return Err(ContractError::StageClosed { stage });
+    }
+
+    // indexed stages flag claims by leaf index
+    let leaf_indexes: Vec<Option<u64>> =
+        match (STAGE_INDEXED.may_load(deps.storage, stage)?.unwrap_or(false), leaf_indexes) {
+            (true, None) => return Err(ContractError::LeafIndexRequired { stage }),
+            (true, Some(indexes)) if indexes.len() == addresses.len() => {
+                indexes.into_iter().map(Some).collect()
+            }
+            (false, None) => vec![None; addresses.len()],
+            _ => return Err(ContractError::InvalidInput {}),
+        };
+
+    // claimed allocations are gone, only the others stay with the stage
+    let mut excluded = vec![];
+    for (address, leaf_index) in addresses.into_iter().zip(leaf_indexes) {
+        let address = tree_address(deps.as_ref(), stage, &address)?;
+        if is_claimed(deps.storage, stage, &address, leaf_index)? {
+            continue;
+        }
+        EXCLUDED.save(deps.storage, (stage, &address), &true)?;
+        excluded.push(address.into_string());
+    }
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "exclude"),
+        attr("stage", stage.to_string()),
+        attr("addresses", excluded.join(",")),
+    ]))
+}
+
+pub fn execute_add_claim_hook(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    address: String,
+) -> Result<Response, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+
+    // if owner set validate, otherwise unauthorized
+    let owner = cfg.owner.ok_or(ContractError::Unauthorized {})?;
+    if info.sender != owner {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let hook = deps.api.addr_validate(&address)?;
+    CLAIM_HOOKS.add_hook(deps.storage, hook)?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "add_claim_hook"),
+        attr("address", address),
+    ]))
+}
+
+pub fn execute_remove_claim_hook(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    address: String,
+) -> Result<Response, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+
+    // if owner set validate, otherwise unauthorized
+    let owner = cfg.owner.ok_or(ContractError::Unauthorized {})?;
+    if info.sender != owner {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let hook = deps.api.addr_validate(&address)?;
+    CLAIM_HOOKS.remove_hook(deps.storage, hook)?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "remove_claim_hook"),
+        attr("address", address),
+    ]))
+}
+
+pub fn execute_vote_hook(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: VoteHookMsg,
+) -> Result<Response, ContractError> {
+    // only proposal modules of tranche stages report votes
+    if !VOTE_MODULES.has(deps.storage, &info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+    let VoteHookMsg::NewVote {
+        proposal_id, voter, ..
+    } = msg;
+    let voter = deps.api.addr_validate(&voter)?;
+
+    // changing a vote does not count as another one
+    let key = (&info.sender, &voter, proposal_id);
+    if !VOTES.has(deps.storage, key) {
+        VOTES.save(deps.storage, key, &true)?;
+        VOTE_HEIGHTS.update(
+            deps.storage,
+            (&info.sender, &voter, env.block.height),
+            |count| -> StdResult<_> { Ok(count.unwrap_or_default() + 1) },
+        )?;
+    }
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "vote_hook"),
+        attr("proposal_module", info.sender),
+        attr("proposal_id", proposal_id.to_string()),
+        attr("voter", voter),
+    ]))
+}
+
+pub fn execute_withdraw_unclaimed(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    stage: u32,
+    recipient: String,
+    amount: Option<Uint128>,
+) -> Result<Response, ContractError> {
+    let recipient = deps.api.addr_validate(&recipient)?;
+    let (config, remaining) = close_stage(deps.branch(), &env, &info, stage, amount)?;
+
+    let mut res = Response::new();
+    if !remaining.is_zero() {
+        res = res.add_message(payout_msg(deps.as_ref(), &config, stage, &recipient, remaining)?);
+    }
+    Ok(res.add_attributes(vec![
+        attr("action", "withdraw_unclaimed"),
+        attr("stage", stage.to_string()),
+        attr("recipient", recipient),
+        attr("amount", remaining),
+    ]))
+}
+
+pub fn execute_burn_unclaimed(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    stage: u32,
+    amount: Option<Uint128>,
+) -> Result<Response, ContractError> {
+    let (config, remaining) = close_stage(deps.branch(), &env, &info, stage, amount)?;
+
+    let mut res = Response::new();
+    if !remaining.is_zero() {
+        let token = stage_token(deps.as_ref(), &config, stage)?;
+        res = res.add_message(token.burn_msg(remaining)?);
+    }
+    Ok(res.add_attributes(vec![
+        attr("action", "burn_unclaimed"),
+        attr("stage", stage.to_string()),
+        attr("amount", remaining),
+    ]))
+}
+
+/// Closes an expired stage on behalf of the owner or a withdrawer and returns the amount left
+/// unclaimed. Stages without a total amount take it from `amount`, up to the contract balance
+/// not owed to the other open stages of their token.
+fn close_stage(
+    deps: DepsMut,
+    env: &Env,
+    info: &MessageInfo,
+    stage: u32,
+    amount: Option<Uint128>,
+) -> Result<(Config, Uint128), ContractError> {
+    authorize(deps.storage, &info.sender, Role::Withdrawer)?;
+    let config = CONFIG.load(deps.storage)?;
+
+    let expiration = STAGE_EXPIRATION.load(deps.storage, stage)?;
+    if !expiration.is_expired(&env.block) {
+        return Err(ContractError::StageNotExpired { stage });
+    }
+    if STAGE_CLOSED.may_load(deps.storage, stage)?.unwrap_or(false) {
+        return Err(ContractError::StageClosed { stage });
+    }
+
+    let remaining = match (STAGE_AMOUNT.has(deps.storage, stage), amount) {
+        (true, None) => {
+            let funded = STAGE_FUNDED.may_load(deps.storage, stage)?.unwrap_or_default();
+            let already_claimed = ALREADY_CLAIMED.may_load(deps.storage, stage)?.unwrap_or_default();
+            funded.saturating_sub(already_claimed)
+        }
+        (true, Some(_)) => return Err(ContractError::InvalidInput {}),
+        (false, None) => return Err(ContractError::MissingTotalAmount {}),
+        (false, Some(amount)) => {
+            // the balance owed to the other open stages of the token stays with them
+            let token = stage_token(deps.as_ref(), &config, stage)?;
+            let balance = token.balance(deps.as_ref(), &env.contract.address)?;
+            let required = token_owed(deps.as_ref(), &config, &token)? + amount;
+            if balance < required {
+                return Err(ContractError::InsufficientFunds {
+                    denom: token.denom(),
+                    balance,
+                    required,
+                });
+            }
+            amount
+        }
+    };
+
+    STAGE_CLOSED.save(deps.storage, stage, &true)?;
+
+    Ok((config, remaining))
+}
+
+/// Builds the messages paying a claim. On vesting stages the liquid share is transferred and
+/// the rest is sent to the vesting contract, registering a vesting account starting at the claim
+/// with the stage's cliff.
+fn claim_msgs(
+    deps: Deps,
+    env: &Env,
+    config: &Config,
+    stage: u32,
+    recipient: &Addr,
+    amount: Uint128,
+) -> StdResult<Vec<CosmosMsg>> {
+    let vesting = match STAGE_VESTING.may_load(deps.storage, stage)? {
+        Some(vesting) => vesting,
+        None => return Ok(vec![payout_msg(deps, config, stage, recipient, amount)?]),
+    };
+
+    let liquid_amount = amount * vesting.liquid_percent;
+    let vesting_amount = amount - liquid_amount;
+
+    let mut msgs = vec![];
+    if !liquid_amount.is_zero() {
+        msgs.push(payout_msg(deps, config, stage, recipient, liquid_amount)?);
+    }
+    if !vesting_amount.is_zero() {
+        let register = VestingReceiveMsg::RegisterVestingAccount {
+            address: recipient.clone(),
+            vesting_amount,
+            prevesting_amount: Uint128::zero(),
+            start_time: env.block.time,
+            end_time: env.block.time.plus_seconds(vesting.duration),
+            cliff_time: Some(env.block.time.plus_seconds(vesting.cliff)),
+        };
+        msgs.push(stage_token(deps, config, stage)?.send_msg(
+            &vesting.vesting_contract,
+            vesting_amount,
+            to_binary(&register)?,
+        )?);
+    }
+    Ok(msgs)
+}
+
+/// Builds the message paying `amount` of the stage's token to `recipient`.
+fn payout_msg(
+    deps: Deps,
+    config: &Config,
+    stage: u32,
+    recipient: &Addr,
+    amount: Uint128,
+) -> StdResult<CosmosMsg> {
+    stage_token(deps, config, stage)?.transfer_msg(recipient, amount)
+}
+
+/// Sends the decayed part of a claim to the stage's treasury.
+fn forfeit_msg(
+    deps: Deps,
+    config: &Config,
+    stage: u32,
+    forfeited: Uint128,
+) -> StdResult<CosmosMsg> {
+    let decay = STAGE_DECAY.load(deps.storage, stage)?;
+    payout_msg(deps, config, stage, &decay.treasury, forfeited)
+}
+
+/// Token a stage is paid out in.
+#[derive(Clone, Debug, PartialEq)]
+enum StageToken {
+    Native(String),
+    Cw20(Addr),
+}
+
+impl StageToken {
+    /// Native denom or cw20 contract address.
+    fn denom(&self) -> String {
+        match self {
+            StageToken::Native(denom) => denom.clone(),
+            StageToken::Cw20(token) => token.to_string(),
+        }
+    }
+
+    fn balance(&self, deps: Deps, address: &Addr) -> StdResult<Uint128> {
+        match self {
+            StageToken::Native(denom) => Ok(deps.querier.query_balance(address, denom)?.amount),
+            StageToken::Cw20(token) => {
+                let res: BalanceResponse = deps.querier.query_wasm_smart(
+                    token,
+                    &Cw20QueryMsg::Balance {
+                        address: address.to_string(),
+                    },
+                )?;
+                Ok(res.balance)
+            }
+        }
+    }
+
+    fn transfer_msg(&self, recipient: &Addr, amount: Uint128) -> StdResult<CosmosMsg> {
+        let msg = match self {
+            StageToken::Native(denom) => BankMsg::Send {
+                to_address: recipient.to_string(),
+                amount: coins(amount.u128(), denom),
+            }
+            .into(),
+            StageToken::Cw20(token) => WasmMsg::Execute {
+                contract_addr: token.to_string(),
+                funds: vec![],
+                msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: recipient.to_string(),
+                    amount,
+                })?,
+            }
+            .into(),
+        };
+        Ok(msg)
+    }
+
+    /// Sends cw20 tokens to a contract along with `msg`, native tokens can't be sent this way.
+    fn send_msg(&self, contract: &Addr, amount: Uint128, msg: Binary) -> StdResult<CosmosMsg> {
+        match self {
+            StageToken::Native(_) => Err(StdError::generic_err("native stage tokens can't be sent")),
+            StageToken::Cw20(token) => Ok(WasmMsg::Execute {
+                contract_addr: token.to_string(),
+                funds: vec![],
+                msg: to_binary(&Cw20ExecuteMsg::Send {
+                    contract: contract.to_string(),
+                    amount,
+                    msg,
+                })?,
+            }
+            .into()),
+        }
+    }
+
+    fn burn_msg(&self, amount: Uint128) -> StdResult<CosmosMsg> {
+        let msg = match self {
+            StageToken::Native(denom) => BankMsg::Burn {
+                amount: coins(amount.u128(), denom),
+            }
+            .into(),
+            StageToken::Cw20(token) => WasmMsg::Execute {
+                contract_addr: token.to_string(),
+                funds: vec![],
+                msg: to_binary(&Cw20ExecuteMsg::Burn { amount })?,
+            }
+            .into(),
+        };
+        Ok(msg)
+    }
+}
+
+fn stage_token(deps: Deps, config: &Config, stage: u32) -> StdResult<StageToken> {
+    let token = match STAGE_NATIVE_DENOM.may_load(deps.storage, stage)? {
+        Some(denom) => StageToken::Native(denom),
+        None => StageToken::Cw20(
+            STAGE_TOKEN
+                .may_load(deps.storage, stage)?
+                .unwrap_or_else(|| config.cw20_token_address.clone()),
+        ),
+    };
+    Ok(token)
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
     match msg {
         QueryMsg::Config {} => to_binary(&query_config(deps)?),
         QueryMsg::MerkleRoot { stage } => to_binary(&query_merkle_root(deps, stage)?),
         QueryMsg::LatestStage {} => to_binary(&query_latest_stage(deps)?),
-        QueryMsg::IsClaimed { stage, address } => {
-            to_binary(&query_is_claimed(deps, stage, address)?)
-        },
+        QueryMsg::IsClaimed {
+            stage,
+            address,
+            leaf_index,
+        } => to_binary(&query_is_claimed(deps, stage, address, leaf_index)?),
         QueryMsg::AlreadyClaimed { stage } => to_binary(&query_already_claimed(deps, stage)?),
+        QueryMsg::StageStats { stage } => to_binary(&query_stage_stats(deps, stage)?),
+        QueryMsg::VerifyClaim {
+            stage,
+            address,
+            amount,
+            proof,
+            leaf_index,
+            voter,
+        } => to_binary(&query_verify_claim(
+            deps, env, stage, address, amount, proof, leaf_index, voter,
+        )?),
+        QueryMsg::ListClaims {
+            stage,
+            start_after,
+            limit,
+        } => to_binary(&query_list_claims(deps, stage, start_after, limit)?),
+        QueryMsg::Roles { address } => to_binary(&query_roles(deps, address)?),
+        QueryMsg::RoleMembers {
+            role,
+            start_after,
+            limit,
+        } => to_binary(&query_role_members(deps, role, start_after, limit)?),
+        QueryMsg::ClaimHooks {} => to_binary(&CLAIM_HOOKS.query_hooks(deps)?),
+        QueryMsg::ListStages { start_after, limit } => {
+            to_binary(&query_list_stages(deps, env, start_after, limit)?)
+        }
+        QueryMsg::Excluded {
+            stage,
+            start_after,
+            limit,
+        } => to_binary(&query_excluded(deps, stage, start_after, limit)?),
     }
 }
 
@@ -224,15 +1525,71 @@ pub fn query_config(deps: Deps) -> StdResult<ConfigResponse> {
     let cfg = CONFIG.load(deps.storage)?;
     Ok(ConfigResponse {
         owner: cfg.owner.map(|o| o.to_string()),
+        pending_owner: PENDING_OWNER.may_load(deps.storage)?.map(Addr::into_string),
         cw20_token_address: cfg.cw20_token_address.to_string(),
     })
 }
 
-pub fn query_merkle_root(deps: Deps, stage: u8) -> StdResult<MerkleRootResponse> {
+pub fn query_roles(deps: Deps, address: String) -> StdResult<RolesResponse> {
+    let address = deps.api.addr_validate(&address)?;
+    let roles = [Role::Registrar, Role::Pauser, Role::Withdrawer]
+        .iter()
+        .filter(|role| ROLES.has(deps.storage, (role.key(), &address)))
+        .copied()
+        .collect();
+    Ok(RolesResponse { roles })
+}
+
+pub fn query_role_members(
+    deps: Deps,
+    role: Role,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<RoleMembersResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(|address| Bound::ExclusiveRaw(address.into_bytes()));
+    let addresses = ROLES
+        .prefix(role.key())
+        .keys(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|address| address.map(Addr::into_string))
+        .collect::<StdResult<_>>()?;
+    Ok(RoleMembersResponse { addresses })
+}
+
+pub fn query_merkle_root(deps: Deps, stage: u32) -> StdResult<MerkleRootResponse> {
     let merkle_root = MERKLE_ROOT.load(deps.storage, stage)?;
     let expiration = STAGE_EXPIRATION.load(deps.storage, stage)?;
     let start = STAGE_START.may_load(deps.storage, stage)?;
-    let resp = MerkleRootResponse { stage, merkle_root, expiration, start };
+    let native_denom = STAGE_NATIVE_DENOM.may_load(deps.storage, stage)?;
+    let total_amount = STAGE_AMOUNT.may_load(deps.storage, stage)?;
+    let paused = STAGE_PAUSED.may_load(deps.storage, stage)?.unwrap_or(false);
+    let vesting = STAGE_VESTING.may_load(deps.storage, stage)?;
+    let cross_chain = STAGE_CROSS_CHAIN.may_load(deps.storage, stage)?;
+    let decay = STAGE_DECAY.may_load(deps.storage, stage)?;
+    let tranches = STAGE_TRANCHES.may_load(deps.storage, stage)?;
+    let indexed_leaves = STAGE_INDEXED.may_load(deps.storage, stage)?.unwrap_or(false);
+    let metadata = STAGE_METADATA.may_load(deps.storage, stage)?;
+    let cw20_token_address = match stage_token(deps, &CONFIG.load(deps.storage)?, stage)? {
+        StageToken::Cw20(token) => Some(token.into_string()),
+        StageToken::Native(_) => None,
+    };
+    let resp = MerkleRootResponse {
+        stage,
+        merkle_root,
+        expiration,
+        start,
+        native_denom,
+        cw20_token_address,
+        total_amount,
+        paused,
+        vesting,
+        cross_chain,
+        decay,
+        tranches,
+        indexed_leaves,
+        metadata,
+    };
 
     Ok(resp)
 }
@@ -244,15 +1601,170 @@ pub fn query_latest_stage(deps: Deps) -> StdResult<LatestStageResponse> {
     Ok(resp)
 }
 
-pub fn query_is_claimed(deps: Deps, stage: u8, address: String) -> StdResult<IsClaimedResponse> {
-    let key: (&Addr, u8) = (&deps.api.addr_validate(&address)?, stage.into());
-    let is_claimed = CLAIM.may_load(deps.storage, key)?.unwrap_or(false);
+pub fn query_is_claimed(
+    deps: Deps,
+    stage: u32,
+    address: String,
+    leaf_index: Option<u64>,
+) -> StdResult<IsClaimedResponse> {
+    let address = tree_address(deps, stage, &address)?;
+    let is_claimed = is_claimed(deps.storage, stage, &address, leaf_index)?;
     let resp = IsClaimedResponse { is_claimed };
 
     Ok(resp)
 }
 
-pub fn query_already_claimed(deps: Deps, stage: u8) -> StdResult<AlreadyClaimedResponse> {
+/// Returns the address as listed in the stage's merkle tree.
+fn tree_address(deps: Deps, stage: u32, address: &str) -> StdResult<Addr> {
+    // external addresses are not valid on this chain
+    match STAGE_CROSS_CHAIN.may_load(deps.storage, stage)? {
+        Some(chain) => Ok(Addr::unchecked(tree_external_address(&chain, address))),
+        None => deps.api.addr_validate(address),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn query_verify_claim(
+    deps: Deps,
+    env: Env,
+    stage: u32,
+    address: String,
+    amount: Uint128,
+    proof: Vec<String>,
+    leaf_index: Option<u64>,
+    voter: Option<String>,
+) -> StdResult<VerifyClaimResponse> {
+    let address = tree_address(deps, stage, &address)?;
+    let expiration = STAGE_EXPIRATION.load(deps.storage, stage)?;
+    let started = STAGE_START
+        .may_load(deps.storage, stage)?
+        .map_or(true, |start| start.is_triggered(&env.block));
+    // malformed proofs are reported as invalid
+    let proof_valid =
+        verify_proof(deps.storage, stage, &address, amount, proof, leaf_index).unwrap_or(false);
+    // on tranche stages only the unlocked unpaid tranches are due, counting the voter's votes
+    let (due, claimed, tranches_paid, tranches_unlocked) =
+        match STAGE_TRANCHES.may_load(deps.storage, stage)? {
+            Some(tranches) => {
+                let voter = match voter {
+                    Some(voter) => deps.api.addr_validate(&voter)?,
+                    None => address.clone(),
+                };
+                let (due, paid, unlocked) =
+                    tranche_due(deps.storage, &tranches, stage, &address, &voter, amount)?;
+                let claimed = paid as usize == tranches.tranches.len();
+                (due, claimed, Some(paid), Some(unlocked))
+            }
+            None => {
+                let claimed = is_claimed(deps.storage, stage, &address, leaf_index)?;
+                (amount, claimed, None, None)
+            }
+        };
+
+    Ok(VerifyClaimResponse {
+        proof_valid,
+        started,
+        expired: expiration.is_expired(&env.block),
+        claimed,
+        excluded: EXCLUDED.has(deps.storage, (stage, &address)),
+        claimable_amount: claimable_amount(deps.storage, &env.block, stage, due)?,
+        tranches_paid,
+        tranches_unlocked,
+    })
+}
+
+pub fn query_list_claims(
+    deps: Deps,
+    stage: u32,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<ListClaimsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(|address| Bound::ExclusiveRaw(address.into_bytes()));
+    let claims = CLAIM
+        .prefix(stage)
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            item.map(|(address, claim)| ClaimResponse {
+                address: address.into_string(),
+                amount: claim.amount,
+                height: claim.height,
+                time: claim.time,
+            })
+        })
+        .collect::<StdResult<_>>()?;
+
+    Ok(ListClaimsResponse { claims })
+}
+
+pub fn query_list_stages(
+    deps: Deps,
+    env: Env,
+    start_after: Option<u32>,
+    limit: Option<u32>,
+) -> StdResult<ListStagesResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+    let stages = MERKLE_ROOT
+        .keys(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|stage| {
+            let stage = stage?;
+            let metadata = STAGE_METADATA.may_load(deps.storage, stage)?;
+            let (title, description, total_leaves) = metadata
+                .map_or((None, None, None), |m| (m.title, m.description, m.total_leaves));
+            Ok(StageResponse {
+                stage,
+                title,
+                description,
+                total_leaves,
+                claimed_amount: ALREADY_CLAIMED
+                    .may_load(deps.storage, stage)?
+                    .unwrap_or_default(),
+                status: stage_status(deps.storage, &env.block, stage)?,
+            })
+        })
+        .collect::<StdResult<_>>()?;
+
+    Ok(ListStagesResponse { stages })
+}
+
+fn stage_status(storage: &dyn Storage, block: &BlockInfo, stage: u32) -> StdResult<StageStatus> {
+    if STAGE_CLOSED.may_load(storage, stage)?.unwrap_or(false) {
+        return Ok(StageStatus::Closed);
+    }
+    if STAGE_EXPIRATION.load(storage, stage)?.is_expired(block) {
+        return Ok(StageStatus::Expired);
+    }
+    let begun = STAGE_START
+        .may_load(storage, stage)?
+        .map_or(true, |start| start.is_triggered(block));
+    if !begun {
+        return Ok(StageStatus::Pending);
+    }
+    Ok(StageStatus::Active)
+}
+
+pub fn query_excluded(
+    deps: Deps,
+    stage: u32,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<ExcludedResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(|address| Bound::ExclusiveRaw(address.into_bytes()));
+    let addresses = EXCLUDED
+        .prefix(stage)
+        .keys(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|address| address.map(Addr::into_string))
+        .collect::<StdResult<_>>()?;
+
+    Ok(ExcludedResponse { addresses })
+}
+
+pub fn query_already_claimed(deps: Deps, stage: u32) -> StdResult<AlreadyClaimedResponse> {
     let already_claimed = ALREADY_CLAIMED.may_load(deps.storage, stage.into())?.unwrap_or(Default::default());
     Ok(AlreadyClaimedResponse {
         stage,
@@ -260,6 +1772,19 @@ pub fn query_already_claimed(deps: Deps, stage: u8) -> StdResult<AlreadyClaimedR
     })
 }
 
+pub fn query_stage_stats(deps: Deps, stage: u32) -> StdResult<StageStatsResponse> {
+    let total_amount = STAGE_AMOUNT.may_load(deps.storage, stage)?.unwrap_or_default();
+    let funded_amount = STAGE_FUNDED.may_load(deps.storage, stage)?.unwrap_or_default();
+    let claimed_amount = ALREADY_CLAIMED.may_load(deps.storage, stage)?.unwrap_or_default();
+    Ok(StageStatsResponse {
+        stage,
+        total_amount,
+        funded_amount,
+        claimed_amount,
+        remaining_amount: funded_amount.saturating_sub(claimed_amount),
+    })
+}
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
     let version = get_contract_version(deps.storage)?;
@@ -268,291 +1793,2323 @@ pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, C
             previous_contract: version.contract,
         });
     }
+
+    // stages were u8 before 0.8.0
+    let previous: Vec<u64> = version
+        .version
+        .split('.')
+        .map(|v| v.parse().unwrap_or_default())
+        .collect();
+    if previous < vec![0, 8, 0] {
+        migrate_stage_keys(deps.storage)?;
+    }
+    // claims were keyed by address first and only flagged before 0.9.0
+    if previous < vec![0, 9, 0] {
+        migrate_claims(deps.storage)?;
+    }
+
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
     Ok(Response::default())
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
-    use cosmwasm_std::{from_binary, from_slice, CosmosMsg, SubMsg};
-    use hex::FromHexError;
-    use serde::Deserialize;
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn reply(deps: DepsMut, _env: Env, msg: Reply) -> Result<Response, ContractError> {
+    // Claim hook failed, remove it unless it already failed for another claim of the transaction
+    let hook = CLAIM_HOOK_REPLIES.load(deps.storage, msg.id)?;
+    let hooks = CLAIM_HOOKS.query_hooks(deps.as_ref())?.hooks;
+    if !hooks.contains(&hook.to_string()) {
+        return Ok(Response::new());
+    }
+    CLAIM_HOOKS.remove_hook(deps.storage, hook.clone())?;
 
-    #[test]
-    fn proper_instantiation() {
-        let mut deps = mock_dependencies();
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "remove_claim_hook"),
+        attr("address", hook),
+    ]))
+}
+
+/// Rewrites the u8 stage keys of all stage maps as u32 keys.
+fn migrate_stage_keys(storage: &mut dyn Storage) -> StdResult<()> {
+    migrate_stage_map::<Expiration>(storage, STAGE_EXPIRATION_KEY)?;
+    migrate_stage_map::<Scheduled>(storage, STAGE_START_KEY)?;
+    migrate_stage_map::<String>(storage, STAGE_NATIVE_DENOM_KEY)?;
+    migrate_stage_map::<Uint128>(storage, STAGE_AMOUNT_KEY)?;
+    migrate_stage_map::<Uint128>(storage, STAGE_FUNDED_KEY)?;
+    migrate_stage_map::<StageVesting>(storage, STAGE_VESTING_KEY)?;
+    migrate_stage_map::<bool>(storage, STAGE_PAUSED_KEY)?;
+    migrate_stage_map::<bool>(storage, STAGE_CLOSED_KEY)?;
+    migrate_stage_map::<CrossChain>(storage, STAGE_CROSS_CHAIN_KEY)?;
+    migrate_stage_map::<String>(storage, MERKLE_ROOT_PREFIX)?;
+    migrate_stage_map::<Uint128>(storage, ALREADY_CLAIMED_PREFIX)?;
+    Ok(())
+}
+
+/// Moves the flags of the address first claim map, with u8 or u32 stages, to the stage first
+/// claim records. Claimed amounts and times were not recorded, they are migrated as zero.
+fn migrate_claims(storage: &mut dyn Storage) -> StdResult<()> {
+    let legacy_u8: Map<(&Addr, u8), bool> = Map::new(LEGACY_CLAIM_PREFIX);
+    let legacy: Map<(&Addr, u32), bool> = Map::new(LEGACY_CLAIM_PREFIX);
+    let claims = legacy
+        .range_raw(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    for (key, _) in claims {
+        // the address is length prefixed, the stage follows it
+        let addr_len = u16::from_be_bytes([key[0], key[1]]) as usize;
+        let addr = Addr::unchecked(String::from_utf8(key[2..addr_len + 2].to_vec())?);
+        let stage = match key[addr_len + 2..] {
+            [stage] => {
+                legacy_u8.remove(storage, (&addr, stage));
+                stage.into()
+            }
+            [a, b, c, d] => {
+                let stage = u32::from_be_bytes([a, b, c, d]);
+                legacy.remove(storage, (&addr, stage));
+                stage
+            }
+            _ => continue,
+        };
+        let claim = ClaimRecord {
+            amount: Uint128::zero(),
+            height: 0,
+            time: Timestamp::from_seconds(0),
+        };
+        CLAIM.save(storage, (stage, &addr), &claim)?;
+    }
+    Ok(())
+}
+
+fn migrate_stage_map<T>(storage: &mut dyn Storage, namespace: &str) -> StdResult<()>
+where
+    T: Serialize + DeserializeOwned,
+{
+    let legacy: Map<u8, T> = Map::new(namespace);
+    let map: Map<u32, T> = Map::new(namespace);
+    let entries = legacy
+        .range_raw(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    for (key, value) in entries {
+        if let [stage] = key[..] {
+            legacy.remove(storage, stage);
+            map.save(storage, stage.into(), &value)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::testing::{mock_dependencies, mock_dependencies_with_balance, mock_env, mock_info};
+    use cosmwasm_std::{from_slice, ContractResult, CosmosMsg, SubMsg, SystemResult, WasmQuery};
+    use cosmwasm_std::SubMsgResult;
+    use hex::FromHexError;
+    use indexable_hooks::{HookError, HooksResponse};
+    use serde::Deserialize;
+
+    #[test]
+    fn proper_instantiation() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg {
+            owner: Some("owner0000".to_string()),
+            cw20_token_address: "anchor0000".to_string(),
+        };
+
+        let env = mock_env();
+        let info = mock_info("addr0000", &[]);
+
+        // we can just call .unwrap() to assert this was a success
+        let _res = instantiate(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+        // it worked, let's query the state
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::Config {}).unwrap();
+        let config: ConfigResponse = from_binary(&res).unwrap();
+        assert_eq!("owner0000", config.owner.unwrap().as_str());
+        assert_eq!("anchor0000", config.cw20_token_address.as_str());
+
+        let res = query(deps.as_ref(), env, QueryMsg::LatestStage {}).unwrap();
+        let latest_stage: LatestStageResponse = from_binary(&res).unwrap();
+        assert_eq!(0u32, latest_stage.latest_stage);
+    }
+
+    #[test]
+    fn update_config() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg {
+            owner: None,
+            cw20_token_address: "anchor0000".to_string(),
+        };
+
+        let env = mock_env();
+        let info = mock_info("owner0000", &[]);
+        let _res = instantiate(deps.as_mut(), env, info, msg).unwrap();
+
+        // update owner
+        let env = mock_env();
+        let info = mock_info("owner0000", &[]);
+        let msg = ExecuteMsg::UpdateConfig {
+            new_owner: Some("owner0001".to_string()),
+        };
+
+        let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+        assert_eq!(0, res.messages.len());
+
+        // proposed owner is pending until accepting
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::Config {}).unwrap();
+        let config: ConfigResponse = from_binary(&res).unwrap();
+        assert_eq!("owner0000", config.owner.unwrap().as_str());
+        assert_eq!("owner0001", config.pending_owner.unwrap().as_str());
+
+        // only the proposed owner can accept
+        let info = mock_info("owner0000", &[]);
+        let msg = ExecuteMsg::AcceptOwnership {};
+        let res = execute(deps.as_mut(), env.clone(), info, msg.clone()).unwrap_err();
+        assert_eq!(res, ContractError::Unauthorized {});
+
+        let info = mock_info("owner0001", &[]);
+        execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+        // it worked, let's query the state
+        let res = query(deps.as_ref(), env, QueryMsg::Config {}).unwrap();
+        let config: ConfigResponse = from_binary(&res).unwrap();
+        assert_eq!("owner0001", config.owner.unwrap().as_str());
+        assert_eq!(None, config.pending_owner);
+
+        // Unauthorized err
+        let env = mock_env();
+        let info = mock_info("owner0000", &[]);
+        let msg = ExecuteMsg::UpdateConfig { new_owner: None };
+
+        let res = execute(deps.as_mut(), env, info, msg).unwrap_err();
+        assert_eq!(res, ContractError::Unauthorized {});
+    }
+
+    #[test]
+    fn register_merkle_root() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg {
+            owner: Some("owner0000".to_string()),
+            cw20_token_address: "anchor0000".to_string(),
+        };
+
+        let env = mock_env();
+        let info = mock_info("addr0000", &[]);
+        let _res = instantiate(deps.as_mut(), env, info, msg).unwrap();
+
+        // register new merkle root
+        let env = mock_env();
+        let info = mock_info("owner0000", &[]);
+        let msg = ExecuteMsg::RegisterMerkleRoot {
+            merkle_root: "634de21cde1044f41d90373733b0f0fb1c1c71f9652b905cdf159e73c4cf0d37"
+                .to_string(),
+            expiration: None,
+            start: None,
+            native_denom: None,
+            cw20_token_address: None,
+            total_amount: Some(Uint128::new(1_000)),
+            vesting: None,
+            cross_chain: None,
+            decay: None,
+            tranches: None,
+            indexed_leaves: None,
+            metadata: None,
+        };
+
+        let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+        assert_eq!(
+            res.attributes,
+            vec![
+                attr("action", "register_merkle_root"),
+                attr("stage", "1"),
+                attr(
+                    "merkle_root",
+                    "634de21cde1044f41d90373733b0f0fb1c1c71f9652b905cdf159e73c4cf0d37"
+                ),
+                attr("total_amount", "1000"),
+            ]
+        );
+
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::LatestStage {}).unwrap();
+        let latest_stage: LatestStageResponse = from_binary(&res).unwrap();
+        assert_eq!(1u32, latest_stage.latest_stage);
+
+        let res = query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::MerkleRoot {
+                stage: latest_stage.latest_stage,
+            },
+        )
+        .unwrap();
+        let merkle_root: MerkleRootResponse = from_binary(&res).unwrap();
+        assert_eq!(
+            "634de21cde1044f41d90373733b0f0fb1c1c71f9652b905cdf159e73c4cf0d37".to_string(),
+            merkle_root.merkle_root
+        );
+
+        // messages without a total amount register an untracked stage, as before totals existed
+        let msg: ExecuteMsg = from_slice(
+            br#"{"register_merkle_root": {
+                "merkle_root": "634de21cde1044f41d90373733b0f0fb1c1c71f9652b905cdf159e73c4cf0d37"
+            }}"#,
+        )
+        .unwrap();
+        let info = mock_info("owner0000", &[]);
+        let res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        assert_eq!(res.attributes.len(), 3);
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::MerkleRoot { stage: 2 }).unwrap();
+        let merkle_root: MerkleRootResponse = from_binary(&res).unwrap();
+        assert_eq!(merkle_root.total_amount, None);
+
+        // native stages are funded on registration and need it
+        let msg = ExecuteMsg::RegisterMerkleRoot {
+            merkle_root: merkle_root.merkle_root,
+            expiration: None,
+            start: None,
+            native_denom: Some("ujuno".to_string()),
+            cw20_token_address: None,
+            total_amount: None,
+            vesting: None,
+            cross_chain: None,
+            decay: None,
+            tranches: None,
+            indexed_leaves: None,
+            metadata: None,
+        };
+        let res = execute(deps.as_mut(), env, info, msg).unwrap_err();
+        assert_eq!(res, ContractError::MissingTotalAmount {});
+    }
+
+    const TEST_DATA_1: &[u8] = include_bytes!("../testdata/airdrop_stage_1_test_data.json");
+    const TEST_DATA_2: &[u8] = include_bytes!("../testdata/airdrop_stage_2_test_data.json");
+    const TEST_DATA_2_FAILURE: &[u8] = include_bytes!("../testdata/airdrop_stage_2_test_data_failure.json");
+    const TEST_DATA_INDEXED: &[u8] = include_bytes!("../testdata/airdrop_indexed_test_data.json");
+
+    #[derive(Deserialize, Debug)]
+    struct Encoded {
+        account: String,
+        amount: Uint128,
+        root: String,
+        proofs: Vec<String>,
+        index: Option<u64>,
+    }
+
+    fn fund_stage(deps: DepsMut, stage: u32, amount: Uint128) {
+        let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "owner0000".to_string(),
+            amount,
+            msg: to_binary(&ReceiveMsg::FundStage { stage }).unwrap(),
+        });
+        execute(deps, mock_env(), mock_info("token0000", &[]), msg).unwrap();
+    }
+
+    #[test]
+    fn claim() {
+        // Run test 1
+        let mut deps = mock_dependencies();
+        let test_data: Encoded = from_slice(TEST_DATA_1).unwrap();
+
+        let msg = InstantiateMsg {
+            owner: Some("owner0000".to_string()),
+            cw20_token_address: "token0000".to_string(),
+        };
+
+        let env = mock_env();
+        let info = mock_info("addr0000", &[]);
+        let _res = instantiate(deps.as_mut(), env, info, msg).unwrap();
+
+        let env = mock_env();
+        let info = mock_info("owner0000", &[]);
+        let msg = ExecuteMsg::RegisterMerkleRoot {
+            merkle_root: test_data.root,
+            expiration: None,
+            start: None,
+            native_denom: None,
+            cw20_token_address: None,
+            total_amount: Some(Uint128::new(1_000)),
+            vesting: None,
+            cross_chain: None,
+            decay: None,
+            tranches: None,
+            indexed_leaves: None,
+            metadata: None,
+        };
+        let _res = execute(deps.as_mut(), env, info, msg).unwrap();
+        fund_stage(deps.as_mut(), 1, Uint128::new(1_000));
+
+        let msg = ExecuteMsg::Claim {
+            amount: test_data.amount,
+            stage: 1u32,
+            proof: test_data.proofs,
+            sig_info: None,
+            leaf_index: None,
+        };
+
+        let env = mock_env();
+        let info = mock_info(test_data.account.as_str(), &[]);
+        let res = execute(deps.as_mut(), env.clone(), info.clone(), msg.clone()).unwrap();
+        let expected = SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "token0000".to_string(),
+            funds: vec![],
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: test_data.account.clone(),
+                amount: test_data.amount,
+            })
+            .unwrap(),
+        }));
+        assert_eq!(res.messages, vec![expected]);
+
+        assert_eq!(
+            res.attributes,
+            vec![
+                attr("action", "claim"),
+                attr("stage", "1"),
+                attr("address", test_data.account.clone()),
+                attr("amount", test_data.amount)
+            ]
+        );
+
+        assert!(
+            from_binary::<IsClaimedResponse>(
+                &query(
+                    deps.as_ref(),
+                    env.clone(),
+                    QueryMsg::IsClaimed {
+                        stage: 1,
+                        address: test_data.account,
+                        leaf_index: None,
+                    }
+                )
+                .unwrap()
+            )
+            .unwrap()
+            .is_claimed
+        );
+
+        let query_msg = QueryMsg::AlreadyClaimed { stage: 1u32 };
+        let res = query(deps.as_ref(), env.clone(), query_msg).unwrap();
+        let val = from_binary::<AlreadyClaimedResponse>(&res).unwrap();
+        assert_eq!(val.stage, 1u32);
+        assert_eq!(val.amount, test_data.amount.clone());
+
+        // Second test
+
+        let test_data: Encoded = from_slice(TEST_DATA_2).unwrap();
+        // check claimed
+        let res = execute(deps.as_mut(), env, info, msg).unwrap_err();
+        assert_eq!(res, ContractError::Claimed {});
+
+
+        // register new drop
+        let env = mock_env();
+        let info = mock_info("owner0000", &[]);
+        let msg = ExecuteMsg::RegisterMerkleRoot {
+            merkle_root: test_data.root,
+            expiration: None,
+            start: None,
+            native_denom: None,
+            cw20_token_address: None,
+            total_amount: Some(Uint128::new(1_000)),
+            vesting: None,
+            cross_chain: None,
+            decay: None,
+            tranches: None,
+            indexed_leaves: None,
+            metadata: None,
+        };
+        let _res = execute(deps.as_mut(), env, info, msg).unwrap();
+        fund_stage(deps.as_mut(), 2, Uint128::new(1_000));
+
+        // Claim next airdrop
+        let msg = ExecuteMsg::Claim {
+            amount: test_data.amount.clone(),
+            stage: 2u32,
+            proof: test_data.proofs.clone(),
+            sig_info: None,
+            leaf_index: None,
+        };
+
+        let env = mock_env();
+        let info = mock_info(test_data.account.clone().as_str(), &[]);
+        let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+        let expected: SubMsg<_> = SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "token0000".to_string(),
+            funds: vec![],
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: test_data.account.clone(),
+                amount: test_data.amount,
+            })
+            .unwrap(),
+        }));
+        assert_eq!(res.messages, vec![expected]);
+
+        assert_eq!(
+            res.attributes,
+            vec![
+                attr("action", "claim"),
+                attr("stage", "2"),
+                attr("address", test_data.account.clone()),
+                attr("amount", test_data.amount.clone())
+            ]
+        );
+
+        let query_msg = QueryMsg::AlreadyClaimed { stage: 2u32 };
+        let res = query(deps.as_ref(), env.clone(), query_msg).unwrap();
+        let val = from_binary::<AlreadyClaimedResponse>(&res).unwrap();
+        assert_eq!(val.stage, 2u32);
+        assert_eq!(val.amount, test_data.amount.clone());
+
+        // Third test - failure to claim
+        // Claim next airdrop
+        let test_data: Encoded = from_slice(TEST_DATA_2_FAILURE).unwrap();
+        let msg = ExecuteMsg::Claim {
+            amount: test_data.amount.clone(),
+            stage: 2u32,
+            proof: test_data.proofs.clone(),
+            sig_info: None,
+            leaf_index: None,
+        };
+
+        let env = mock_env();
+        let info = mock_info(test_data.account.clone().as_str(), &[]);
+        let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap_err();
+        assert_eq!(res, ContractError::Hex { 0: FromHexError::InvalidHexCharacter { c: 'z', index: 0 } });
+    }
+
+    #[test]
+    fn claim_native() {
+        let test_data: Encoded = from_slice(TEST_DATA_1).unwrap();
+        let mut deps = mock_dependencies_with_balance(&coins(10_000, "ujuno"));
+
+        let msg = InstantiateMsg {
+            owner: Some("owner0000".to_string()),
+            cw20_token_address: "token0000".to_string(),
+        };
+
+        let env = mock_env();
+        let info = mock_info("addr0000", &[]);
+        let _res = instantiate(deps.as_mut(), env, info, msg).unwrap();
+
+        // can't register more than the contract holds
+        let env = mock_env();
+        let info = mock_info("owner0000", &[]);
+        let msg = ExecuteMsg::RegisterMerkleRoot {
+            merkle_root: test_data.root.clone(),
+            expiration: None,
+            start: None,
+            native_denom: Some("ujuno".to_string()),
+            cw20_token_address: None,
+            total_amount: Some(Uint128::new(10_001)),
+            vesting: None,
+            cross_chain: None,
+            decay: None,
+            tranches: None,
+            indexed_leaves: None,
+            metadata: None,
+        };
+        let res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap_err();
+        assert_eq!(
+            res,
+            ContractError::InsufficientFunds {
+                denom: "ujuno".to_string(),
+                balance: Uint128::new(10_000),
+                required: Uint128::new(10_001),
+            }
+        );
+
+        let msg = ExecuteMsg::RegisterMerkleRoot {
+            merkle_root: test_data.root.clone(),
+            expiration: None,
+            start: None,
+            native_denom: Some("ujuno".to_string()),
+            cw20_token_address: None,
+            total_amount: Some(Uint128::new(10_000)),
+            vesting: None,
+            cross_chain: None,
+            decay: None,
+            tranches: None,
+            indexed_leaves: None,
+            metadata: None,
+        };
+        execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+        // the balance backing stage 1 can't back another stage
+        let msg = ExecuteMsg::RegisterMerkleRoot {
+            merkle_root: test_data.root,
+            expiration: None,
+            start: None,
+            native_denom: Some("ujuno".to_string()),
+            cw20_token_address: None,
+            total_amount: Some(Uint128::new(1)),
+            vesting: None,
+            cross_chain: None,
+            decay: None,
+            tranches: None,
+            indexed_leaves: None,
+            metadata: None,
+        };
+        let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap_err();
+        assert_eq!(
+            res,
+            ContractError::InsufficientFunds {
+                denom: "ujuno".to_string(),
+                balance: Uint128::new(10_000),
+                required: Uint128::new(10_001),
+            }
+        );
+
+        let res = query(deps.as_ref(), env, QueryMsg::MerkleRoot { stage: 1 }).unwrap();
+        let merkle_root: MerkleRootResponse = from_binary(&res).unwrap();
+        assert_eq!(merkle_root.native_denom, Some("ujuno".to_string()));
+        assert_eq!(merkle_root.total_amount, Some(Uint128::new(10_000)));
+
+        // claim is paid with a bank send
+        let msg = ExecuteMsg::Claim {
+            amount: test_data.amount,
+            stage: 1u32,
+            proof: test_data.proofs,
+            sig_info: None,
+            leaf_index: None,
+        };
+        let env = mock_env();
+        let info = mock_info(test_data.account.as_str(), &[]);
+        let res = execute(deps.as_mut(), env, info, msg).unwrap();
+        let expected = SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+            to_address: test_data.account,
+            amount: coins(test_data.amount.u128(), "ujuno"),
+        }));
+        assert_eq!(res.messages, vec![expected]);
+    }
+
+    #[test]
+    fn stage_funding() {
+        let test_data: Encoded = from_slice(TEST_DATA_1).unwrap();
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg {
+            owner: Some("owner0000".to_string()),
+            cw20_token_address: "token0000".to_string(),
+        };
+        let env = mock_env();
+        let info = mock_info("addr0000", &[]);
+        let _res = instantiate(deps.as_mut(), env, info, msg).unwrap();
+
+        let env = mock_env();
+        let info = mock_info("owner0000", &[]);
+        let msg = ExecuteMsg::RegisterMerkleRoot {
+            merkle_root: test_data.root,
+            expiration: None,
+            start: None,
+            native_denom: None,
+            cw20_token_address: None,
+            total_amount: Some(Uint128::new(150)),
+            vesting: None,
+            cross_chain: None,
+            decay: None,
+            tranches: None,
+            indexed_leaves: None,
+            metadata: None,
+        };
+        execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+        // only the stage token can fund it
+        let fund = |amount: u128| {
+            ExecuteMsg::Receive(Cw20ReceiveMsg {
+                sender: "owner0000".to_string(),
+                amount: Uint128::new(amount),
+                msg: to_binary(&ReceiveMsg::FundStage { stage: 1 }).unwrap(),
+            })
+        };
+        let info = mock_info("other0000", &[]);
+        let res = execute(deps.as_mut(), env.clone(), info, fund(50)).unwrap_err();
+        assert_eq!(res, ContractError::Unauthorized {});
+
+        let info = mock_info("token0000", &[]);
+        execute(deps.as_mut(), env.clone(), info.clone(), fund(50)).unwrap();
+        let res = execute(deps.as_mut(), env.clone(), info, fund(101)).unwrap_err();
+        assert_eq!(res, ContractError::StageOverfunded { stage: 1 });
+
+        // claim exceeds the funded amount
+        let msg = ExecuteMsg::Claim {
+            amount: test_data.amount,
+            stage: 1u32,
+            proof: test_data.proofs,
+            sig_info: None,
+            leaf_index: None,
+        };
+        let info = mock_info(test_data.account.as_str(), &[]);
+        let res = execute(deps.as_mut(), env.clone(), info.clone(), msg.clone()).unwrap_err();
+        assert_eq!(res, ContractError::StageUnderfunded { stage: 1 });
+
+        fund_stage(deps.as_mut(), 1, Uint128::new(100));
+        execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+        let res = query(deps.as_ref(), env, QueryMsg::StageStats { stage: 1 }).unwrap();
+        let stats: StageStatsResponse = from_binary(&res).unwrap();
+        assert_eq!(
+            stats,
+            StageStatsResponse {
+                stage: 1,
+                total_amount: Uint128::new(150),
+                funded_amount: Uint128::new(150),
+                claimed_amount: Uint128::new(100),
+                remaining_amount: Uint128::new(50),
+            }
+        );
+    }
+
+    #[test]
+    fn claim_stage_token() {
+        let test_data: Encoded = from_slice(TEST_DATA_1).unwrap();
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg {
+            owner: Some("owner0000".to_string()),
+            cw20_token_address: "token0000".to_string(),
+        };
+        let env = mock_env();
+        let info = mock_info("addr0000", &[]);
+        let _res = instantiate(deps.as_mut(), env, info, msg).unwrap();
+
+        let env = mock_env();
+        let info = mock_info("owner0000", &[]);
+        let register = |native_denom: Option<String>, cw20_token_address: Option<String>| {
+            ExecuteMsg::RegisterMerkleRoot {
+                merkle_root: test_data.root.clone(),
+                expiration: None,
+                start: None,
+                native_denom,
+                cw20_token_address,
+                total_amount: Some(Uint128::new(1_000)),
+                vesting: None,
+                cross_chain: None,
+                decay: None,
+                tranches: None,
+                indexed_leaves: None,
+                metadata: None,
+            }
+        };
+
+        // a stage pays out in one token
+        let msg = register(Some("ujuno".to_string()), Some("partner0000".to_string()));
+        let res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap_err();
+        assert_eq!(res, ContractError::InvalidInput {});
+
+        let msg = register(None, Some("partner0000".to_string()));
+        let res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        assert!(res
+            .attributes
+            .contains(&attr("cw20_token_address", "partner0000")));
+        execute(deps.as_mut(), env.clone(), info, register(None, None)).unwrap();
+
+        for (stage, token) in [(1, "partner0000"), (2, "token0000")] {
+            let res = query(deps.as_ref(), env.clone(), QueryMsg::MerkleRoot { stage }).unwrap();
+            let merkle_root: MerkleRootResponse = from_binary(&res).unwrap();
+            assert_eq!(merkle_root.cw20_token_address, Some(token.to_string()));
+        }
+
+        // only the stage token funds it
+        let fund = ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "owner0000".to_string(),
+            amount: Uint128::new(1_000),
+            msg: to_binary(&ReceiveMsg::FundStage { stage: 1 }).unwrap(),
+        });
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("token0000", &[]),
+            fund.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(res, ContractError::Unauthorized {});
+        execute(deps.as_mut(), env.clone(), mock_info("partner0000", &[]), fund).unwrap();
+
+        let msg = ExecuteMsg::Claim {
+            amount: test_data.amount,
+            stage: 1,
+            proof: test_data.proofs,
+            sig_info: None,
+            leaf_index: None,
+        };
+        let info = mock_info(test_data.account.as_str(), &[]);
+        let res = execute(deps.as_mut(), env, info, msg).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(WasmMsg::Execute {
+                contract_addr: "partner0000".to_string(),
+                funds: vec![],
+                msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: test_data.account,
+                    amount: test_data.amount,
+                })
+                .unwrap(),
+            })]
+        );
+    }
+
+    #[test]
+    fn claim_exceeds_total() {
+        let test_data: Encoded = from_slice(TEST_DATA_1).unwrap();
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg {
+            owner: Some("owner0000".to_string()),
+            cw20_token_address: "token0000".to_string(),
+        };
+        let env = mock_env();
+        let info = mock_info("addr0000", &[]);
+        let _res = instantiate(deps.as_mut(), env, info, msg).unwrap();
+
+        let env = mock_env();
+        let info = mock_info("owner0000", &[]);
+        let msg = ExecuteMsg::RegisterMerkleRoot {
+            merkle_root: test_data.root,
+            expiration: None,
+            start: None,
+            native_denom: None,
+            cw20_token_address: None,
+            total_amount: Some(Uint128::new(99)),
+            vesting: None,
+            cross_chain: None,
+            decay: None,
+            tranches: None,
+            indexed_leaves: None,
+            metadata: None,
+        };
+        execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+        fund_stage(deps.as_mut(), 1, Uint128::new(99));
+
+        let msg = ExecuteMsg::Claim {
+            amount: test_data.amount,
+            stage: 1u32,
+            proof: test_data.proofs,
+            sig_info: None,
+            leaf_index: None,
+        };
+        let info = mock_info(test_data.account.as_str(), &[]);
+        let res = execute(deps.as_mut(), env, info, msg).unwrap_err();
+        assert_eq!(res, ContractError::StageAmountExceeded { stage: 1 });
+    }
+
+    #[test]
+    fn withdraw_unclaimed() {
+        let test_data: Encoded = from_slice(TEST_DATA_1).unwrap();
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg {
+            owner: Some("owner0000".to_string()),
+            cw20_token_address: "token0000".to_string(),
+        };
+        let env = mock_env();
+        let info = mock_info("addr0000", &[]);
+        let _res = instantiate(deps.as_mut(), env, info, msg).unwrap();
+
+        let mut env = mock_env();
+        let info = mock_info("owner0000", &[]);
+        let msg = ExecuteMsg::RegisterMerkleRoot {
+            merkle_root: test_data.root,
+            expiration: Some(Expiration::AtHeight(env.block.height + 100)),
+            start: None,
+            native_denom: None,
+            cw20_token_address: None,
+            total_amount: Some(Uint128::new(1_000_000)),
+            vesting: None,
+            cross_chain: None,
+            decay: None,
+            tranches: None,
+            indexed_leaves: None,
+            metadata: None,
+        };
+        execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+        fund_stage(deps.as_mut(), 1, Uint128::new(1_000_000));
+
+        let msg = ExecuteMsg::Claim {
+            amount: test_data.amount,
+            stage: 1u32,
+            proof: test_data.proofs,
+            sig_info: None,
+            leaf_index: None,
+        };
+        let info = mock_info(test_data.account.as_str(), &[]);
+        execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+        // can't withdraw before expiration
+        let msg = ExecuteMsg::WithdrawUnclaimed {
+            stage: 1,
+            recipient: "treasury0000".to_string(),
+            amount: None,
+        };
+        let info = mock_info("owner0000", &[]);
+        let res = execute(deps.as_mut(), env.clone(), info, msg.clone()).unwrap_err();
+        assert_eq!(res, ContractError::StageNotExpired { stage: 1 });
+
+        // only owner
+        env.block.height += 100;
+        let info = mock_info("addr0000", &[]);
+        let res = execute(deps.as_mut(), env.clone(), info, msg.clone()).unwrap_err();
+        assert_eq!(res, ContractError::Unauthorized {});
+
+        let info = mock_info("owner0000", &[]);
+        let res = execute(deps.as_mut(), env.clone(), info.clone(), msg.clone()).unwrap();
+        let remaining = Uint128::new(1_000_000) - test_data.amount;
+        let expected = SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "token0000".to_string(),
+            funds: vec![],
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: "treasury0000".to_string(),
+                amount: remaining,
+            })
+            .unwrap(),
+        }));
+        assert_eq!(res.messages, vec![expected]);
+
+        // stage is closed
+        let res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap_err();
+        assert_eq!(res, ContractError::StageClosed { stage: 1 });
+        let res = execute(deps.as_mut(), env, info, ExecuteMsg::BurnUnclaimed { stage: 1, amount: None })
+            .unwrap_err();
+        assert_eq!(res, ContractError::StageClosed { stage: 1 });
+    }
+
+    #[test]
+    fn withdraw_unclaimed_legacy_stage() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg {
+            owner: Some("owner0000".to_string()),
+            cw20_token_address: "token0000".to_string(),
+        };
+        let env = mock_env();
+        let info = mock_info("addr0000", &[]);
+        let _res = instantiate(deps.as_mut(), env, info, msg).unwrap();
+
+        // stage registered before total amounts were tracked
+        let mut env = mock_env();
+        LATEST_STAGE.save(&mut deps.storage, &1).unwrap();
+        MERKLE_ROOT.save(&mut deps.storage, 1, &"root".to_string()).unwrap();
+        STAGE_EXPIRATION
+            .save(&mut deps.storage, 1, &Expiration::AtHeight(env.block.height))
+            .unwrap();
+
+        // an open stage of the same token still owes 150 of the balance
+        LATEST_STAGE.save(&mut deps.storage, &2).unwrap();
+        MERKLE_ROOT.save(&mut deps.storage, 2, &"root".to_string()).unwrap();
+        STAGE_EXPIRATION
+            .save(&mut deps.storage, 2, &Expiration::AtHeight(env.block.height + 100))
+            .unwrap();
+        STAGE_AMOUNT.save(&mut deps.storage, 2, &Uint128::new(200)).unwrap();
+        STAGE_FUNDED.save(&mut deps.storage, 2, &Uint128::new(200)).unwrap();
+        ALREADY_CLAIMED.save(&mut deps.storage, 2, &Uint128::new(50)).unwrap();
+        deps.querier.update_wasm(|query| match query {
+            WasmQuery::Smart { contract_addr, .. } if contract_addr == "token0000" => SystemResult::Ok(
+                ContractResult::Ok(to_binary(&BalanceResponse { balance: Uint128::new(500) }).unwrap()),
+            ),
+            _ => unimplemented!(),
+        });
+        env.block.height += 1;
+        let info = mock_info("owner0000", &[]);
+
+        // the amount left is required
+        let msg = ExecuteMsg::WithdrawUnclaimed {
+            stage: 1,
+            recipient: "treasury0000".to_string(),
+            amount: None,
+        };
+        let res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap_err();
+        assert_eq!(res, ContractError::MissingTotalAmount {});
+
+        // up to the contract balance not owed to the other stage
+        let msg = ExecuteMsg::WithdrawUnclaimed {
+            stage: 1,
+            recipient: "treasury0000".to_string(),
+            amount: Some(Uint128::new(351)),
+        };
+        let res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap_err();
+        assert_eq!(
+            res,
+            ContractError::InsufficientFunds {
+                denom: "token0000".to_string(),
+                balance: Uint128::new(500),
+                required: Uint128::new(501),
+            }
+        );
+
+        let msg = ExecuteMsg::WithdrawUnclaimed {
+            stage: 1,
+            recipient: "treasury0000".to_string(),
+            amount: Some(Uint128::new(350)),
+        };
+        let res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        let expected = SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "token0000".to_string(),
+            funds: vec![],
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: "treasury0000".to_string(),
+                amount: Uint128::new(350),
+            })
+            .unwrap(),
+        }));
+        assert_eq!(res.messages, vec![expected]);
+
+        let msg = ExecuteMsg::BurnUnclaimed { stage: 1, amount: Some(Uint128::new(1)) };
+        let res = execute(deps.as_mut(), env, info, msg).unwrap_err();
+        assert_eq!(res, ContractError::StageClosed { stage: 1 });
+    }
+
+    #[test]
+    fn exclude_addresses() {
+        let test_data: Encoded = from_slice(TEST_DATA_1).unwrap();
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg {
+            owner: Some("owner0000".to_string()),
+            cw20_token_address: "token0000".to_string(),
+        };
+        let info = mock_info("addr0000", &[]);
+        instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let mut env = mock_env();
+        let msg = ExecuteMsg::RegisterMerkleRoot {
+            merkle_root: test_data.root,
+            expiration: Some(Expiration::AtHeight(env.block.height + 100)),
+            start: None,
+            native_denom: None,
+            cw20_token_address: None,
+            total_amount: Some(Uint128::new(1_000)),
+            vesting: None,
+            cross_chain: None,
+            decay: None,
+            tranches: None,
+            indexed_leaves: None,
+            metadata: None,
+        };
+        execute(deps.as_mut(), env.clone(), mock_info("owner0000", &[]), msg).unwrap();
+        fund_stage(deps.as_mut(), 1, Uint128::new(1_000));
+
+        let msg = ExecuteMsg::Exclude {
+            stage: 1,
+            addresses: vec![test_data.account.clone(), "addr0001".to_string()],
+            leaf_indexes: None,
+        };
+        let res = execute(deps.as_mut(), env.clone(), mock_info("addr0000", &[]), msg.clone());
+        assert_eq!(res.unwrap_err(), ContractError::Unauthorized {});
+        execute(deps.as_mut(), env.clone(), mock_info("owner0000", &[]), msg).unwrap();
+
+        let msg = ExecuteMsg::Claim {
+            stage: 1,
+            amount: test_data.amount,
+            proof: test_data.proofs,
+            sig_info: None,
+            leaf_index: None,
+        };
+        let info = mock_info(test_data.account.as_str(), &[]);
+        let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap_err();
+        assert_eq!(
+            res,
+            ContractError::AddressExcluded {
+                stage: 1,
+                address: test_data.account.clone()
+            }
+        );
+
+        // paginated by address
+        let excluded = |start_after: Option<String>| {
+            let msg = QueryMsg::Excluded {
+                stage: 1,
+                start_after,
+                limit: Some(1),
+            };
+            let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+            from_binary::<ExcludedResponse>(&res).unwrap().addresses
+        };
+        assert_eq!(excluded(None), vec!["addr0001".to_string()]);
+        assert_eq!(excluded(Some("addr0001".to_string())), vec![test_data.account.clone()]);
+        assert!(excluded(Some(test_data.account)).is_empty());
+
+        // excluded allocations are withdrawn with the unclaimed funds
+        env.block.height += 100;
+        let msg = ExecuteMsg::WithdrawUnclaimed {
+            stage: 1,
+            recipient: "treasury0000".to_string(),
+            amount: None,
+        };
+        let res = execute(deps.as_mut(), env, mock_info("owner0000", &[]), msg).unwrap();
+        let expected = SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "token0000".to_string(),
+            funds: vec![],
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: "treasury0000".to_string(),
+                amount: Uint128::new(1_000),
+            })
+            .unwrap(),
+        }));
+        assert_eq!(res.messages, vec![expected]);
+    }
+
+    #[test]
+    fn claim_hooks() {
+        let test_data: Encoded = from_slice(TEST_DATA_1).unwrap();
+        let mut deps = mock_dependencies_with_balance(&coins(100, "ujuno"));
+
+        let msg = InstantiateMsg {
+            owner: Some("owner0000".to_string()),
+            cw20_token_address: "token0000".to_string(),
+        };
+        let info = mock_info("addr0000", &[]);
+        instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let msg = ExecuteMsg::RegisterMerkleRoot {
+            merkle_root: test_data.root,
+            expiration: None,
+            start: None,
+            native_denom: Some("ujuno".to_string()),
+            cw20_token_address: None,
+            total_amount: Some(Uint128::new(100)),
+            vesting: None,
+            cross_chain: None,
+            decay: None,
+            tranches: None,
+            indexed_leaves: None,
+            metadata: None,
+        };
+        execute(deps.as_mut(), mock_env(), mock_info("owner0000", &[]), msg).unwrap();
+
+        // only owner
+        let add_hook = |address: &str| ExecuteMsg::AddClaimHook {
+            address: address.to_string(),
+        };
+        let info = mock_info("addr0000", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, add_hook("quest0000")).unwrap_err();
+        assert_eq!(res, ContractError::Unauthorized {});
+        let info = mock_info("owner0000", &[]);
+        execute(deps.as_mut(), mock_env(), info.clone(), add_hook("quest0000")).unwrap();
+        execute(deps.as_mut(), mock_env(), info.clone(), add_hook("boost0000")).unwrap();
+        let res = execute(deps.as_mut(), mock_env(), info, add_hook("quest0000")).unwrap_err();
+        assert_eq!(res, ContractError::HookError(HookError::HookAlreadyRegistered {}));
+
+        let msg = ExecuteMsg::Claim {
+            stage: 1,
+            amount: test_data.amount,
+            proof: test_data.proofs,
+            sig_info: None,
+            leaf_index: None,
+        };
+        let info = mock_info(test_data.account.as_str(), &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        let hook_msg = to_binary(&ClaimHookExecuteMsg::ClaimHook(ClaimHookMsg {
+            stage: 1,
+            address: test_data.account.clone(),
+            amount: test_data.amount,
+        }))
+        .unwrap();
+        let hook = |address: &str, index: u64| {
+            let execute = WasmMsg::Execute {
+                contract_addr: address.to_string(),
+                msg: hook_msg.clone(),
+                funds: vec![],
+            };
+            SubMsg::reply_on_error(execute, index)
+        };
+        assert_eq!(res.messages[1..], [hook("quest0000", 0), hook("boost0000", 1)]);
+
+        // failing hooks are all removed, also when failing again for another claim
+        for id in [0, 1, 0] {
+            let msg = Reply {
+                id,
+                result: SubMsgResult::Err("quest failed".to_string()),
+            };
+            reply(deps.as_mut(), mock_env(), msg).unwrap();
+        }
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::ClaimHooks {}).unwrap();
+        let hooks: HooksResponse = from_binary(&res).unwrap();
+        assert!(hooks.hooks.is_empty());
+    }
+
+    #[test]
+    fn list_stages() {
+        let test_data: Encoded = from_slice(TEST_DATA_1).unwrap();
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg {
+            owner: Some("owner0000".to_string()),
+            cw20_token_address: "token0000".to_string(),
+        };
+        let info = mock_info("addr0000", &[]);
+        instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let mut env = mock_env();
+        let height = env.block.height;
+        let register = |start, expiration, metadata| ExecuteMsg::RegisterMerkleRoot {
+            merkle_root: test_data.root.clone(),
+            expiration,
+            start,
+            native_denom: None,
+            cw20_token_address: None,
+            total_amount: Some(Uint128::new(1_000)),
+            vesting: None,
+            cross_chain: None,
+            decay: None,
+            tranches: None,
+            indexed_leaves: None,
+            metadata,
+        };
+        let metadata = StageMetadata {
+            title: Some("Genesis".to_string()),
+            description: Some("Stakers at genesis".to_string()),
+            total_leaves: Some(3),
+        };
+
+        let info = mock_info("owner0000", &[]);
+        let empty_title = StageMetadata {
+            title: Some("".to_string()),
+            ..metadata.clone()
+        };
+        let msg = register(None, None, Some(empty_title));
+        let res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap_err();
+        assert_eq!(res, ContractError::InvalidInput {});
+
+        let msg = register(None, None, Some(metadata.clone()));
+        execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        let msg = register(Some(Scheduled::AtHeight(height + 10)), None, None);
+        execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        for _ in 0..2 {
+            let msg = register(None, Some(Expiration::AtHeight(height + 5)), None);
+            execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        }
+        env.block.height += 5;
+        let msg = ExecuteMsg::BurnUnclaimed { stage: 4, amount: None };
+        execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+        let msg = ExecuteMsg::Claim {
+            stage: 1,
+            amount: test_data.amount,
+            proof: test_data.proofs.clone(),
+            sig_info: None,
+            leaf_index: None,
+        };
+        fund_stage(deps.as_mut(), 1, Uint128::new(1_000));
+        let info = mock_info(test_data.account.as_str(), &[]);
+        execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::MerkleRoot { stage: 1 }).unwrap();
+        let merkle_root: MerkleRootResponse = from_binary(&res).unwrap();
+        assert_eq!(merkle_root.metadata, Some(metadata));
+
+        let list = |start_after, limit| {
+            let msg = QueryMsg::ListStages { start_after, limit };
+            let res = query(deps.as_ref(), env.clone(), msg).unwrap();
+            from_binary::<ListStagesResponse>(&res).unwrap().stages
+        };
+        let stages = list(None, None);
+        assert_eq!(
+            stages[0],
+            StageResponse {
+                stage: 1,
+                title: Some("Genesis".to_string()),
+                description: Some("Stakers at genesis".to_string()),
+                total_leaves: Some(3),
+                claimed_amount: test_data.amount,
+                status: StageStatus::Active,
+            }
+        );
+        let statuses: Vec<_> = stages.into_iter().map(|s| s.status).collect();
+        assert_eq!(
+            statuses,
+            vec![
+                StageStatus::Active,
+                StageStatus::Pending,
+                StageStatus::Expired,
+                StageStatus::Closed
+            ]
+        );
+
+        // paginated by stage
+        let stages: Vec<_> = list(Some(1), Some(2)).into_iter().map(|s| s.stage).collect();
+        assert_eq!(stages, vec![2, 3]);
+        assert!(list(Some(4), None).is_empty());
+    }
+
+    #[test]
+    fn burn_unclaimed() {
+        let mut deps = mock_dependencies_with_balance(&coins(500, "ujuno"));
+
+        let msg = InstantiateMsg {
+            owner: Some("owner0000".to_string()),
+            cw20_token_address: "token0000".to_string(),
+        };
+        let env = mock_env();
+        let info = mock_info("addr0000", &[]);
+        let _res = instantiate(deps.as_mut(), env, info, msg).unwrap();
+
+        let mut env = mock_env();
+        let info = mock_info("owner0000", &[]);
+        let msg = ExecuteMsg::RegisterMerkleRoot {
+            merkle_root: "5d4f48f147cb6cb742b376dce5626b2a036f69faec10cd73631c791780e150fc"
+                .to_string(),
+            expiration: Some(Expiration::AtHeight(env.block.height + 1)),
+            start: None,
+            native_denom: Some("ujuno".to_string()),
+            cw20_token_address: None,
+            total_amount: Some(Uint128::new(500)),
+            vesting: None,
+            cross_chain: None,
+            decay: None,
+            tranches: None,
+            indexed_leaves: None,
+            metadata: None,
+        };
+        execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+        env.block.height += 1;
+        let res = execute(deps.as_mut(), env, info, ExecuteMsg::BurnUnclaimed { stage: 1, amount: None }).unwrap();
+        let expected = SubMsg::new(CosmosMsg::Bank(BankMsg::Burn {
+            amount: coins(500, "ujuno"),
+        }));
+        assert_eq!(res.messages, vec![expected]);
+    }
+
+    #[test]
+    fn pause_stage() {
+        let test_data: Encoded = from_slice(TEST_DATA_1).unwrap();
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg {
+            owner: Some("owner0000".to_string()),
+            cw20_token_address: "token0000".to_string(),
+        };
+        let env = mock_env();
+        let info = mock_info("addr0000", &[]);
+        let _res = instantiate(deps.as_mut(), env, info, msg).unwrap();
+
+        let env = mock_env();
+        let info = mock_info("owner0000", &[]);
+        let msg = ExecuteMsg::RegisterMerkleRoot {
+            merkle_root: test_data.root,
+            expiration: None,
+            start: None,
+            native_denom: None,
+            cw20_token_address: None,
+            total_amount: Some(Uint128::new(1_000)),
+            vesting: None,
+            cross_chain: None,
+            decay: None,
+            tranches: None,
+            indexed_leaves: None,
+            metadata: None,
+        };
+        execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+        fund_stage(deps.as_mut(), 1, Uint128::new(1_000));
+
+        // only owner can pause
+        let info = mock_info("addr0000", &[]);
+        let res = execute(deps.as_mut(), env.clone(), info, ExecuteMsg::PauseStage { stage: 1 })
+            .unwrap_err();
+        assert_eq!(res, ContractError::Unauthorized {});
+
+        let owner = mock_info("owner0000", &[]);
+        execute(deps.as_mut(), env.clone(), owner.clone(), ExecuteMsg::PauseStage { stage: 1 })
+            .unwrap();
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::MerkleRoot { stage: 1 }).unwrap();
+        let merkle_root: MerkleRootResponse = from_binary(&res).unwrap();
+        assert!(merkle_root.paused);
+
+        let msg = ExecuteMsg::Claim {
+            amount: test_data.amount,
+            stage: 1u32,
+            proof: test_data.proofs,
+            sig_info: None,
+            leaf_index: None,
+        };
+        let info = mock_info(test_data.account.as_str(), &[]);
+        let res = execute(deps.as_mut(), env.clone(), info.clone(), msg.clone()).unwrap_err();
+        assert_eq!(res, ContractError::StagePaused { stage: 1 });
+
+        execute(deps.as_mut(), env.clone(), owner, ExecuteMsg::ResumeStage { stage: 1 }).unwrap();
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::MerkleRoot { stage: 1 }).unwrap();
+        let merkle_root: MerkleRootResponse = from_binary(&res).unwrap();
+        assert!(!merkle_root.paused);
+        execute(deps.as_mut(), env, info, msg).unwrap();
+    }
+
+    #[test]
+    fn claim_vesting() {
+        let test_data: Encoded = from_slice(TEST_DATA_1).unwrap();
+        let mut deps = mock_dependencies();
+        deps.querier.update_wasm(|query| match query {
+            WasmQuery::Smart { contract_addr, .. } if contract_addr == "vesting0000" => {
+                SystemResult::Ok(ContractResult::Ok(
+                    to_binary(&TokenAddressResponse {
+                        token_address: Addr::unchecked("token0000"),
+                    })
+                    .unwrap(),
+                ))
+            }
+            _ => SystemResult::Ok(ContractResult::Ok(
+                to_binary(&TokenAddressResponse {
+                    token_address: Addr::unchecked("token0001"),
+                })
+                .unwrap(),
+            )),
+        });
 
         let msg = InstantiateMsg {
             owner: Some("owner0000".to_string()),
-            cw20_token_address: "anchor0000".to_string(),
+            cw20_token_address: "token0000".to_string(),
         };
-
         let env = mock_env();
         let info = mock_info("addr0000", &[]);
+        let _res = instantiate(deps.as_mut(), env, info, msg).unwrap();
 
-        // we can just call .unwrap() to assert this was a success
-        let _res = instantiate(deps.as_mut(), env.clone(), info, msg).unwrap();
+        // vesting contract must vest the stage token
+        let template = |vesting_contract: &str| VestingTemplate {
+            vesting_contract: vesting_contract.to_string(),
+            cliff: 100,
+            duration: 1_000,
+            liquid_percent: Decimal::percent(25),
+        };
+        let env = mock_env();
+        let info = mock_info("owner0000", &[]);
+        let msg = ExecuteMsg::RegisterMerkleRoot {
+            merkle_root: test_data.root.clone(),
+            expiration: None,
+            start: None,
+            native_denom: None,
+            cw20_token_address: None,
+            total_amount: Some(Uint128::new(1_000)),
+            vesting: Some(template("vesting0001")),
+            cross_chain: None,
+            decay: None,
+            tranches: None,
+            indexed_leaves: None,
+            metadata: None,
+        };
+        let res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap_err();
+        assert_eq!(res, ContractError::InvalidInput {});
 
-        // it worked, let's query the state
-        let res = query(deps.as_ref(), env.clone(), QueryMsg::Config {}).unwrap();
-        let config: ConfigResponse = from_binary(&res).unwrap();
-        assert_eq!("owner0000", config.owner.unwrap().as_str());
-        assert_eq!("anchor0000", config.cw20_token_address.as_str());
+        // cliff can't be after the end of the vesting
+        let msg = ExecuteMsg::RegisterMerkleRoot {
+            merkle_root: test_data.root.clone(),
+            expiration: None,
+            start: None,
+            native_denom: None,
+            cw20_token_address: None,
+            total_amount: Some(Uint128::new(1_000)),
+            vesting: Some(VestingTemplate {
+                cliff: 1_001,
+                ..template("vesting0000")
+            }),
+            cross_chain: None,
+            decay: None,
+            tranches: None,
+            indexed_leaves: None,
+            metadata: None,
+        };
+        let res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap_err();
+        assert_eq!(res, ContractError::InvalidInput {});
 
-        let res = query(deps.as_ref(), env, QueryMsg::LatestStage {}).unwrap();
-        let latest_stage: LatestStageResponse = from_binary(&res).unwrap();
-        assert_eq!(0u8, latest_stage.latest_stage);
+        let msg = ExecuteMsg::RegisterMerkleRoot {
+            merkle_root: test_data.root,
+            expiration: None,
+            start: None,
+            native_denom: None,
+            cw20_token_address: None,
+            total_amount: Some(Uint128::new(1_000)),
+            vesting: Some(template("vesting0000")),
+            cross_chain: None,
+            decay: None,
+            tranches: None,
+            indexed_leaves: None,
+            metadata: None,
+        };
+        execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+        fund_stage(deps.as_mut(), 1, Uint128::new(1_000));
+
+        let msg = ExecuteMsg::Claim {
+            amount: test_data.amount,
+            stage: 1u32,
+            proof: test_data.proofs,
+            sig_info: None,
+            leaf_index: None,
+        };
+        let info = mock_info(test_data.account.as_str(), &[]);
+        let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::new(WasmMsg::Execute {
+                    contract_addr: "token0000".to_string(),
+                    funds: vec![],
+                    msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                        recipient: test_data.account.clone(),
+                        amount: Uint128::new(25),
+                    })
+                    .unwrap(),
+                }),
+                SubMsg::new(WasmMsg::Execute {
+                    contract_addr: "token0000".to_string(),
+                    funds: vec![],
+                    msg: to_binary(&Cw20ExecuteMsg::Send {
+                        contract: "vesting0000".to_string(),
+                        amount: Uint128::new(75),
+                        msg: to_binary(&VestingReceiveMsg::RegisterVestingAccount {
+                            address: Addr::unchecked(test_data.account),
+                            vesting_amount: Uint128::new(75),
+                            prevesting_amount: Uint128::zero(),
+                            start_time: env.block.time,
+                            end_time: env.block.time.plus_seconds(1_000),
+                            cliff_time: Some(env.block.time.plus_seconds(100)),
+                        })
+                        .unwrap(),
+                    })
+                    .unwrap(),
+                }),
+            ]
+        );
     }
 
     #[test]
-    fn update_config() {
-        let mut deps = mock_dependencies();
+    fn claim_many() {
+        let test_data: Encoded = from_slice(TEST_DATA_1).unwrap();
+        let mut deps = mock_dependencies_with_balance(&coins(1_000, "ujuno"));
 
         let msg = InstantiateMsg {
-            owner: None,
-            cw20_token_address: "anchor0000".to_string(),
+            owner: Some("owner0000".to_string()),
+            cw20_token_address: "token0000".to_string(),
         };
-
         let env = mock_env();
-        let info = mock_info("owner0000", &[]);
+        let info = mock_info("addr0000", &[]);
         let _res = instantiate(deps.as_mut(), env, info, msg).unwrap();
 
-        // update owner
+        // two cw20 stages and a native one with the same tree
         let env = mock_env();
         let info = mock_info("owner0000", &[]);
-        let msg = ExecuteMsg::UpdateConfig {
-            new_owner: Some("owner0001".to_string()),
+        for native_denom in [None, None, Some("ujuno".to_string())] {
+            let msg = ExecuteMsg::RegisterMerkleRoot {
+                merkle_root: test_data.root.clone(),
+                expiration: None,
+                start: None,
+                native_denom,
+                cw20_token_address: None,
+                total_amount: Some(Uint128::new(1_000)),
+                vesting: None,
+                cross_chain: None,
+                decay: None,
+                tranches: None,
+                indexed_leaves: None,
+                metadata: None,
+            };
+            execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        }
+        fund_stage(deps.as_mut(), 1, Uint128::new(1_000));
+        fund_stage(deps.as_mut(), 2, Uint128::new(1_000));
+
+        let info = mock_info(test_data.account.as_str(), &[]);
+        let msg = ExecuteMsg::Claim {
+            amount: test_data.amount,
+            stage: 1u32,
+            proof: test_data.proofs.clone(),
+            sig_info: None,
+            leaf_index: None,
+        };
+        execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+        let claims: Vec<StageClaim> = (1..=3)
+            .map(|stage| StageClaim {
+                stage,
+                amount: test_data.amount,
+                proof: test_data.proofs.clone(),
+                sig_info: None,
+                leaf_index: None,
+            })
+            .collect();
+
+        // already claimed stage fails the batch
+        let msg = ExecuteMsg::ClaimMany {
+            claims: claims.clone(),
+            skip_claimed: false,
         };
+        let res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap_err();
+        assert_eq!(res, ContractError::Claimed {});
 
+        // or is skipped
+        let msg = ExecuteMsg::ClaimMany {
+            claims,
+            skip_claimed: true,
+        };
         let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
-        assert_eq!(0, res.messages.len());
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::new(WasmMsg::Execute {
+                    contract_addr: "token0000".to_string(),
+                    funds: vec![],
+                    msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                        recipient: test_data.account.clone(),
+                        amount: test_data.amount,
+                    })
+                    .unwrap(),
+                }),
+                SubMsg::new(BankMsg::Send {
+                    to_address: test_data.account.clone(),
+                    amount: coins(test_data.amount.u128(), "ujuno"),
+                }),
+            ]
+        );
+        assert_eq!(
+            res.attributes,
+            vec![
+                attr("action", "claim_many"),
+                attr("stages", "2,3"),
+                attr("address", test_data.account),
+                attr("amount", Uint128::new(200)),
+            ]
+        );
+    }
 
-        // it worked, let's query the state
-        let res = query(deps.as_ref(), env, QueryMsg::Config {}).unwrap();
-        let config: ConfigResponse = from_binary(&res).unwrap();
-        assert_eq!("owner0001", config.owner.unwrap().as_str());
+    const TEST_DATA_CROSS_CHAIN: &[u8] =
+        include_bytes!("../testdata/airdrop_cross_chain_test_data.json");
 
-        // Unauthorized err
-        let env = mock_env();
-        let info = mock_info("owner0000", &[]);
-        let msg = ExecuteMsg::UpdateConfig { new_owner: None };
+    #[derive(Deserialize, Debug)]
+    struct CrossChainEncoded {
+        recipient: String,
+        root: String,
+        cosmos: ExternalClaim,
+        evm: ExternalClaim,
+    }
 
-        let res = execute(deps.as_mut(), env, info, msg).unwrap_err();
-        assert_eq!(res, ContractError::Unauthorized {});
+    #[derive(Deserialize, Debug)]
+    struct ExternalClaim {
+        address: String,
+        amount: Uint128,
+        pub_key: Option<Binary>,
+        signature: Binary,
+        proofs: Vec<String>,
     }
 
     #[test]
-    fn register_merkle_root() {
+    fn claim_cross_chain() {
+        let test_data: CrossChainEncoded = from_slice(TEST_DATA_CROSS_CHAIN).unwrap();
         let mut deps = mock_dependencies();
 
         let msg = InstantiateMsg {
             owner: Some("owner0000".to_string()),
-            cw20_token_address: "anchor0000".to_string(),
+            cw20_token_address: "token0000".to_string(),
         };
-
         let env = mock_env();
         let info = mock_info("addr0000", &[]);
         let _res = instantiate(deps.as_mut(), env, info, msg).unwrap();
 
-        // register new merkle root
+        // the tree lists both addresses, stage 1 accepts cosmos and stage 2 evm signatures
         let env = mock_env();
         let info = mock_info("owner0000", &[]);
-        let msg = ExecuteMsg::RegisterMerkleRoot {
-            merkle_root: "634de21cde1044f41d90373733b0f0fb1c1c71f9652b905cdf159e73c4cf0d37"
-                .to_string(),
-            expiration: None,
-            start: None,
+        for cross_chain in [
+            CrossChain::Cosmos {
+                hrp: "cosmos".to_string(),
+            },
+            CrossChain::Evm {},
+        ] {
+            let msg = ExecuteMsg::RegisterMerkleRoot {
+                merkle_root: test_data.root.clone(),
+                expiration: None,
+                start: None,
+                native_denom: None,
+                cw20_token_address: None,
+                total_amount: Some(Uint128::new(1_000)),
+                vesting: None,
+                cross_chain: Some(cross_chain),
+                decay: None,
+                tranches: None,
+                indexed_leaves: None,
+                metadata: None,
+            };
+            execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        }
+        fund_stage(deps.as_mut(), 1, Uint128::new(1_000));
+        fund_stage(deps.as_mut(), 2, Uint128::new(1_000));
+
+        let cosmos_sig = SignatureInfo {
+            external_address: test_data.cosmos.address.clone(),
+            pub_key: test_data.cosmos.pub_key.clone(),
+            signature: test_data.cosmos.signature.clone(),
+        };
+        // evm addresses are matched case insensitively
+        let evm_sig = SignatureInfo {
+            external_address: test_data.evm.address.to_uppercase().replacen("0X", "0x", 1),
+            pub_key: None,
+            signature: test_data.evm.signature.clone(),
         };
 
-        let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+        // signature is required
+        let info = mock_info(test_data.recipient.as_str(), &[]);
+        let msg = ExecuteMsg::Claim {
+            stage: 1,
+            amount: test_data.cosmos.amount,
+            proof: test_data.cosmos.proofs.clone(),
+            sig_info: None,
+            leaf_index: None,
+        };
+        let res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap_err();
+        assert_eq!(res, ContractError::SignatureRequired { stage: 1 });
+
+        // signature must be of the sender
+        let msg = ExecuteMsg::Claim {
+            stage: 1,
+            amount: test_data.cosmos.amount,
+            proof: test_data.cosmos.proofs.clone(),
+            sig_info: Some(cosmos_sig.clone()),
+            leaf_index: None,
+        };
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("attacker0000", &[]),
+            msg.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(res, ContractError::VerificationFailed {});
+
+        // evm signature does not pass on the cosmos stage
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            info.clone(),
+            ExecuteMsg::Claim {
+                stage: 1,
+                amount: test_data.evm.amount,
+                proof: test_data.evm.proofs.clone(),
+                sig_info: Some(evm_sig.clone()),
+                leaf_index: None,
+            },
+        )
+        .unwrap_err();
+        assert_eq!(res, ContractError::VerificationFailed {});
+
+        let res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(WasmMsg::Execute {
+                contract_addr: "token0000".to_string(),
+                funds: vec![],
+                msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: test_data.recipient.clone(),
+                    amount: test_data.cosmos.amount,
+                })
+                .unwrap(),
+            })]
+        );
         assert_eq!(
             res.attributes,
             vec![
-                attr("action", "register_merkle_root"),
+                attr("action", "claim"),
                 attr("stage", "1"),
-                attr(
-                    "merkle_root",
-                    "634de21cde1044f41d90373733b0f0fb1c1c71f9652b905cdf159e73c4cf0d37"
-                )
+                attr("address", test_data.recipient.clone()),
+                attr("amount", test_data.cosmos.amount),
+                attr("external_address", test_data.cosmos.address.clone()),
             ]
         );
 
-        let res = query(deps.as_ref(), env.clone(), QueryMsg::LatestStage {}).unwrap();
-        let latest_stage: LatestStageResponse = from_binary(&res).unwrap();
-        assert_eq!(1u8, latest_stage.latest_stage);
-
+        // claim is recorded for the external address
         let res = query(
             deps.as_ref(),
-            env,
-            QueryMsg::MerkleRoot {
-                stage: latest_stage.latest_stage,
+            env.clone(),
+            QueryMsg::IsClaimed {
+                stage: 1,
+                address: test_data.cosmos.address.clone(),
+                leaf_index: None,
             },
         )
         .unwrap();
-        let merkle_root: MerkleRootResponse = from_binary(&res).unwrap();
+        assert!(from_binary::<IsClaimedResponse>(&res).unwrap().is_claimed);
+
+        let msg = ExecuteMsg::ClaimMany {
+            claims: vec![
+                StageClaim {
+                    stage: 1,
+                    amount: test_data.cosmos.amount,
+                    proof: test_data.cosmos.proofs,
+                    sig_info: Some(cosmos_sig),
+                    leaf_index: None,
+                },
+                StageClaim {
+                    stage: 2,
+                    amount: test_data.evm.amount,
+                    proof: test_data.evm.proofs,
+                    sig_info: Some(evm_sig),
+                    leaf_index: None,
+                },
+            ],
+            skip_claimed: true,
+        };
+        let res = execute(deps.as_mut(), env, info, msg).unwrap();
         assert_eq!(
-            "634de21cde1044f41d90373733b0f0fb1c1c71f9652b905cdf159e73c4cf0d37".to_string(),
-            merkle_root.merkle_root
+            res.messages,
+            vec![SubMsg::new(WasmMsg::Execute {
+                contract_addr: "token0000".to_string(),
+                funds: vec![],
+                msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: test_data.recipient,
+                    amount: test_data.evm.amount,
+                })
+                .unwrap(),
+            })]
         );
+
+        // and the claim is recorded for the lowercase address
+        for address in [test_data.evm.address.clone(), test_data.evm.address.to_uppercase()] {
+            let msg = QueryMsg::IsClaimed {
+                stage: 2,
+                address,
+                leaf_index: None,
+            };
+            let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+            assert!(from_binary::<IsClaimedResponse>(&res).unwrap().is_claimed);
+        }
+        let msg = QueryMsg::ListClaims {
+            stage: 2,
+            start_after: None,
+            limit: None,
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let list: ListClaimsResponse = from_binary(&res).unwrap();
+        assert_eq!(list.claims[0].address, test_data.evm.address);
     }
 
-    const TEST_DATA_1: &[u8] = include_bytes!("../testdata/airdrop_stage_1_test_data.json");
-    const TEST_DATA_2: &[u8] = include_bytes!("../testdata/airdrop_stage_2_test_data.json");
-    const TEST_DATA_2_FAILURE: &[u8] = include_bytes!("../testdata/airdrop_stage_2_test_data_failure.json");
+    #[test]
+    fn verify_claim() {
+        let test_data: Encoded = from_slice(TEST_DATA_1).unwrap();
+        let mut deps = mock_dependencies();
 
-    #[derive(Deserialize, Debug)]
-    struct Encoded {
-        account: String,
-        amount: Uint128,
-        root: String,
-        proofs: Vec<String>,
+        let msg = InstantiateMsg {
+            owner: Some("owner0000".to_string()),
+            cw20_token_address: "token0000".to_string(),
+        };
+        let env = mock_env();
+        let info = mock_info("addr0000", &[]);
+        let _res = instantiate(deps.as_mut(), env, info, msg).unwrap();
+
+        let mut env = mock_env();
+        let info = mock_info("owner0000", &[]);
+        let msg = ExecuteMsg::RegisterMerkleRoot {
+            merkle_root: test_data.root.clone(),
+            expiration: Some(Expiration::AtHeight(env.block.height + 100)),
+            start: Some(Scheduled::AtHeight(env.block.height + 10)),
+            native_denom: None,
+            cw20_token_address: None,
+            total_amount: Some(Uint128::new(1_000)),
+            vesting: None,
+            cross_chain: None,
+            decay: None,
+            tranches: None,
+            indexed_leaves: None,
+            metadata: None,
+        };
+        execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+        fund_stage(deps.as_mut(), 1, Uint128::new(1_000));
+
+        let verify = |env: &Env, deps: Deps, amount: Uint128, proof: Vec<String>| {
+            let msg = QueryMsg::VerifyClaim {
+                stage: 1,
+                address: test_data.account.clone(),
+                amount,
+                proof,
+                leaf_index: None,
+                voter: None,
+            };
+            from_binary::<VerifyClaimResponse>(&query(deps, env.clone(), msg).unwrap()).unwrap()
+        };
+
+        assert_eq!(
+            verify(&env, deps.as_ref(), test_data.amount, test_data.proofs.clone()),
+            VerifyClaimResponse {
+                proof_valid: true,
+                started: false,
+                expired: false,
+                claimed: false,
+                excluded: false,
+                claimable_amount: test_data.amount,
+                tranches_paid: None,
+                tranches_unlocked: None,
+            }
+        );
+        // wrong amounts and malformed proofs are invalid
+        let res = verify(&env, deps.as_ref(), Uint128::new(101), test_data.proofs.clone());
+        assert!(!res.proof_valid);
+        let res = verify(&env, deps.as_ref(), test_data.amount, vec!["zz".to_string()]);
+        assert!(!res.proof_valid);
+
+        env.block.height += 10;
+        let msg = ExecuteMsg::Claim {
+            amount: test_data.amount,
+            stage: 1,
+            proof: test_data.proofs.clone(),
+            sig_info: None,
+            leaf_index: None,
+        };
+        let info = mock_info(test_data.account.as_str(), &[]);
+        execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+        env.block.height += 90;
+        assert_eq!(
+            verify(&env, deps.as_ref(), test_data.amount, test_data.proofs.clone()),
+            VerifyClaimResponse {
+                proof_valid: true,
+                started: true,
+                expired: true,
+                claimed: true,
+                excluded: false,
+                claimable_amount: test_data.amount,
+                tranches_paid: None,
+                tranches_unlocked: None,
+            }
+        );
     }
 
     #[test]
-    fn claim() {
-        // Run test 1
+    fn list_claims() {
         let mut deps = mock_dependencies();
-        let test_data: Encoded = from_slice(TEST_DATA_1).unwrap();
 
         let msg = InstantiateMsg {
             owner: Some("owner0000".to_string()),
             cw20_token_address: "token0000".to_string(),
         };
-
         let env = mock_env();
         let info = mock_info("addr0000", &[]);
         let _res = instantiate(deps.as_mut(), env, info, msg).unwrap();
 
+        // two leaf tree, each leaf is the proof of the other
+        let accounts = [("addr0001", Uint128::new(100)), ("addr0002", Uint128::new(200))];
+        let leaves: Vec<[u8; 32]> = accounts
+            .iter()
+            .map(|(address, amount)| {
+                sha2::Sha256::digest(format!("{}{}", address, amount).as_bytes()).into()
+            })
+            .collect();
+        let mut sorted = leaves.clone();
+        sorted.sort_unstable();
+        let root = hex::encode(sha2::Sha256::digest(&sorted.concat()));
+
         let env = mock_env();
         let info = mock_info("owner0000", &[]);
         let msg = ExecuteMsg::RegisterMerkleRoot {
-            merkle_root: test_data.root,
+            merkle_root: root,
             expiration: None,
             start: None,
+            native_denom: None,
+            cw20_token_address: None,
+            total_amount: Some(Uint128::new(1_000)),
+            vesting: None,
+            cross_chain: None,
+            decay: None,
+            tranches: None,
+            indexed_leaves: None,
+            metadata: None,
         };
-        let _res = execute(deps.as_mut(), env, info, msg).unwrap();
+        execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+        fund_stage(deps.as_mut(), 1, Uint128::new(1_000));
+
+        // claimed in reverse order, listed by address
+        let mut env = env;
+        for (i, (address, amount)) in accounts.iter().enumerate().rev() {
+            env.block.height += 1;
+            let msg = ExecuteMsg::Claim {
+                stage: 1,
+                amount: *amount,
+                proof: vec![hex::encode(leaves[1 - i])],
+                sig_info: None,
+                leaf_index: None,
+            };
+            execute(deps.as_mut(), env.clone(), mock_info(address, &[]), msg).unwrap();
+        }
+
+        let list = |start_after: Option<String>, limit: Option<u32>| {
+            let msg = QueryMsg::ListClaims {
+                stage: 1,
+                start_after,
+                limit,
+            };
+            let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+            from_binary::<ListClaimsResponse>(&res).unwrap().claims
+        };
+        let first = ClaimResponse {
+            address: "addr0001".to_string(),
+            amount: Uint128::new(100),
+            height: env.block.height,
+            time: env.block.time,
+        };
+        let second = ClaimResponse {
+            address: "addr0002".to_string(),
+            amount: Uint128::new(200),
+            height: env.block.height - 1,
+            time: env.block.time,
+        };
+        assert_eq!(list(None, None), vec![first.clone(), second.clone()]);
+        assert_eq!(list(None, Some(1)), vec![first]);
+        assert_eq!(list(Some("addr0001".to_string()), None), vec![second]);
+        assert_eq!(list(Some("addr0002".to_string()), None), vec![]);
+    }
+
+    #[test]
+    fn claim_decay() {
+        let test_data: Encoded = from_slice(TEST_DATA_1).unwrap();
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg {
+            owner: Some("owner0000".to_string()),
+            cw20_token_address: "token0000".to_string(),
+        };
+        let env = mock_env();
+        let info = mock_info("addr0000", &[]);
+        let _res = instantiate(deps.as_mut(), env, info, msg).unwrap();
+
+        let mut env = mock_env();
+        let info = mock_info("owner0000", &[]);
+        let decay = DecayTemplate {
+            floor: Decimal::percent(50),
+            treasury: "dao0000".to_string(),
+        };
+        let register = |start: Option<Scheduled>, expiration: Option<Expiration>, floor| {
+            ExecuteMsg::RegisterMerkleRoot {
+                merkle_root: test_data.root.clone(),
+                expiration,
+                start,
+                native_denom: None,
+                cw20_token_address: None,
+                total_amount: Some(Uint128::new(1_000)),
+                vesting: None,
+                cross_chain: None,
+                decay: Some(DecayTemplate {
+                    floor,
+                    treasury: "dao0000".to_string(),
+                }),
+                tranches: None,
+                indexed_leaves: None,
+                metadata: None,
+            }
+        };
+
+        // decay needs a start before the expiration, of the same kind
+        let now = env.block.time;
+        for msg in [
+            register(None, Some(Expiration::AtTime(now.plus_seconds(1_000))), decay.floor),
+            register(
+                Some(Scheduled::AtHeight(env.block.height)),
+                Some(Expiration::AtTime(now.plus_seconds(1_000))),
+                decay.floor,
+            ),
+            register(
+                Some(Scheduled::AtTime(now)),
+                Some(Expiration::AtTime(now)),
+                decay.floor,
+            ),
+            register(
+                Some(Scheduled::AtTime(now)),
+                Some(Expiration::AtTime(now.plus_seconds(1_000))),
+                Decimal::percent(101),
+            ),
+        ] {
+            let res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap_err();
+            assert_eq!(res, ContractError::InvalidInput {});
+        }
+
+        let msg = register(
+            Some(Scheduled::AtTime(now)),
+            Some(Expiration::AtTime(now.plus_seconds(1_000))),
+            decay.floor,
+        );
+        execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+        fund_stage(deps.as_mut(), 1, Uint128::new(1_000));
+
+        // 40% into the stage 20% of the claim is forfeited
+        env.block.time = now.plus_seconds(400);
+        let msg = QueryMsg::VerifyClaim {
+            stage: 1,
+            address: test_data.account.clone(),
+            amount: test_data.amount,
+            proof: test_data.proofs.clone(),
+            leaf_index: None,
+            voter: None,
+        };
+        let res: VerifyClaimResponse =
+            from_binary(&query(deps.as_ref(), env.clone(), msg).unwrap()).unwrap();
+        assert_eq!(res.claimable_amount, Uint128::new(80));
 
         let msg = ExecuteMsg::Claim {
+            stage: 1,
             amount: test_data.amount,
-            stage: 1u8,
             proof: test_data.proofs,
+            sig_info: None,
+            leaf_index: None,
         };
-
-        let env = mock_env();
         let info = mock_info(test_data.account.as_str(), &[]);
-        let res = execute(deps.as_mut(), env.clone(), info.clone(), msg.clone()).unwrap();
-        let expected = SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
-            contract_addr: "token0000".to_string(),
-            funds: vec![],
-            msg: to_binary(&Cw20ExecuteMsg::Transfer {
-                recipient: test_data.account.clone(),
-                amount: test_data.amount,
+        let res = execute(deps.as_mut(), env, info, msg).unwrap();
+        let transfer = |recipient: &str, amount: u128| {
+            SubMsg::new(WasmMsg::Execute {
+                contract_addr: "token0000".to_string(),
+                funds: vec![],
+                msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: recipient.to_string(),
+                    amount: Uint128::new(amount),
+                })
+                .unwrap(),
             })
-            .unwrap(),
-        }));
-        assert_eq!(res.messages, vec![expected]);
-
+        };
+        assert_eq!(
+            res.messages,
+            vec![transfer(&test_data.account, 80), transfer("dao0000", 20)]
+        );
         assert_eq!(
             res.attributes,
             vec![
                 attr("action", "claim"),
                 attr("stage", "1"),
-                attr("address", test_data.account.clone()),
-                attr("amount", test_data.amount)
+                attr("address", test_data.account),
+                attr("amount", "80"),
+                attr("forfeited", "20"),
             ]
         );
 
-        assert!(
-            from_binary::<IsClaimedResponse>(
-                &query(
-                    deps.as_ref(),
-                    env.clone(),
-                    QueryMsg::IsClaimed {
-                        stage: 1,
-                        address: test_data.account
-                    }
-                )
+        // the whole allocation counts as claimed
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::StageStats { stage: 1 }).unwrap();
+        let stats: StageStatsResponse = from_binary(&res).unwrap();
+        assert_eq!(stats.claimed_amount, Uint128::new(100));
+    }
+
+    #[test]
+    fn claim_tranches() {
+        let test_data: Encoded = from_slice(TEST_DATA_1).unwrap();
+        let mut deps = mock_dependencies_with_balance(&coins(200, "ujuno"));
+
+        let msg = InstantiateMsg {
+            owner: Some("owner0000".to_string()),
+            cw20_token_address: "token0000".to_string(),
+        };
+        let info = mock_info("addr0000", &[]);
+        instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let register = |tranches: Vec<Decimal>| ExecuteMsg::RegisterMerkleRoot {
+            merkle_root: test_data.root.clone(),
+            expiration: None,
+            start: None,
+            native_denom: Some("ujuno".to_string()),
+            cw20_token_address: None,
+            total_amount: Some(Uint128::new(100)),
+            vesting: None,
+            cross_chain: None,
+            decay: None,
+            tranches: Some(TranchesTemplate {
+                proposal_module: "proposal0000".to_string(),
+                tranches,
+            }),
+            indexed_leaves: None,
+            metadata: None,
+        };
+
+        // tranches must sum up to the whole allocation
+        let info = mock_info("owner0000", &[]);
+        for tranches in [
+            vec![Decimal::one()],
+            vec![Decimal::percent(50), Decimal::percent(40)],
+            vec![Decimal::zero(), Decimal::one()],
+        ] {
+            let res = execute(deps.as_mut(), mock_env(), info.clone(), register(tranches));
+            assert_eq!(res.unwrap_err(), ContractError::InvalidInput {});
+        }
+        let tranches = vec![Decimal::percent(50), Decimal::percent(30), Decimal::percent(20)];
+        execute(deps.as_mut(), mock_env(), info, register(tranches)).unwrap();
+
+        let claim = || ExecuteMsg::Claim {
+            stage: 1,
+            amount: test_data.amount,
+            proof: test_data.proofs.clone(),
+            sig_info: None,
+            leaf_index: None,
+        };
+        let claimer = mock_info(test_data.account.as_str(), &[]);
+        let paid = |res: Response| {
+            res.attributes
+                .into_iter()
+                .find(|a| a.key == "amount")
                 .unwrap()
-            )
-            .unwrap()
-            .is_claimed
-        );
+                .value
+        };
 
-        let query_msg = QueryMsg::AlreadyClaimed { stage: 1u8 };
-        let res = query(deps.as_ref(), env.clone(), query_msg).unwrap();
-        let val = from_binary::<AlreadyClaimedResponse>(&res).unwrap();
-        assert_eq!(val.stage, 1u8);
-        assert_eq!(val.amount, test_data.amount.clone());
+        // first tranche is paid on claim, the next one needs a vote
+        let res = execute(deps.as_mut(), mock_env(), claimer.clone(), claim()).unwrap();
+        assert_eq!(paid(res), "50");
+        let res = execute(deps.as_mut(), mock_env(), claimer.clone(), claim()).unwrap_err();
+        assert_eq!(res, ContractError::TrancheLocked { stage: 1 });
 
-        // Second test
+        // only the stage's proposal module reports votes
+        let vote = |proposal_id| {
+            ExecuteMsg::VoteHook(VoteHookMsg::NewVote {
+                proposal_id,
+                voter: test_data.account.clone(),
+                vote: "yes".to_string(),
+            })
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("addr0000", &[]), vote(1));
+        assert_eq!(res.unwrap_err(), ContractError::Unauthorized {});
 
-        let test_data: Encoded = from_slice(TEST_DATA_2).unwrap();
-        // check claimed
-        let res = execute(deps.as_mut(), env, info, msg).unwrap_err();
+        // a changed vote on the same proposal unlocks nothing more
+        let mut env = mock_env();
+        env.block.height += 1;
+        let module = mock_info("proposal0000", &[]);
+        execute(deps.as_mut(), env.clone(), module.clone(), vote(1)).unwrap();
+        execute(deps.as_mut(), env.clone(), module.clone(), vote(1)).unwrap();
+
+        let verify = |deps: Deps, voter: Option<&str>| {
+            let msg = QueryMsg::VerifyClaim {
+                stage: 1,
+                address: test_data.account.clone(),
+                amount: test_data.amount,
+                proof: test_data.proofs.clone(),
+                leaf_index: None,
+                voter: voter.map(str::to_string),
+            };
+            from_binary::<VerifyClaimResponse>(&query(deps, mock_env(), msg).unwrap()).unwrap()
+        };
+        let res = verify(deps.as_ref(), None);
+        assert!(!res.claimed);
+        assert_eq!(res.claimable_amount, Uint128::new(30));
+        assert_eq!((res.tranches_paid, res.tranches_unlocked), (Some(1), Some(2)));
+        // votes of another voter, e.g. the claimer of a cross chain stage, are counted instead
+        let res = verify(deps.as_ref(), Some("addr0001"));
+        assert_eq!(res.claimable_amount, Uint128::zero());
+        assert_eq!(res.tranches_unlocked, Some(1));
+
+        let res = execute(deps.as_mut(), mock_env(), claimer.clone(), claim()).unwrap();
+        assert_eq!(paid(res), "30");
+        let res = execute(deps.as_mut(), mock_env(), claimer.clone(), claim()).unwrap_err();
+        assert_eq!(res, ContractError::TrancheLocked { stage: 1 });
+
+        // votes on two more proposals unlock the last tranche once
+        execute(deps.as_mut(), env.clone(), module.clone(), vote(2)).unwrap();
+        execute(deps.as_mut(), env.clone(), module.clone(), vote(3)).unwrap();
+        let res = execute(deps.as_mut(), mock_env(), claimer.clone(), claim()).unwrap();
+        assert_eq!(paid(res), "20");
+        let res = execute(deps.as_mut(), mock_env(), claimer.clone(), claim()).unwrap_err();
         assert_eq!(res, ContractError::Claimed {});
+        assert!(verify(deps.as_ref(), None).claimed);
 
+        let msg = QueryMsg::ListClaims {
+            stage: 1,
+            start_after: None,
+            limit: None,
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let list: ListClaimsResponse = from_binary(&res).unwrap();
+        assert_eq!(list.claims[0].amount, Uint128::new(100));
 
-        // register new drop
-        let env = mock_env();
+        // votes cast before a stage was registered unlock none of its tranches
+        env.block.height += 1;
         let info = mock_info("owner0000", &[]);
+        let tranches = vec![Decimal::percent(50), Decimal::percent(50)];
+        execute(deps.as_mut(), env.clone(), info, register(tranches)).unwrap();
+        let claim = || ExecuteMsg::Claim {
+            stage: 2,
+            amount: test_data.amount,
+            proof: test_data.proofs.clone(),
+            sig_info: None,
+            leaf_index: None,
+        };
+        let res = execute(deps.as_mut(), env.clone(), claimer.clone(), claim()).unwrap();
+        assert_eq!(paid(res), "50");
+        let res = execute(deps.as_mut(), env.clone(), claimer.clone(), claim()).unwrap_err();
+        assert_eq!(res, ContractError::TrancheLocked { stage: 2 });
+
+        env.block.height += 1;
+        execute(deps.as_mut(), env.clone(), module, vote(4)).unwrap();
+        let res = execute(deps.as_mut(), env, claimer, claim()).unwrap();
+        assert_eq!(paid(res), "50");
+    }
+
+    #[test]
+    fn claim_indexed_leaves() {
+        let test_data: Encoded = from_slice(TEST_DATA_INDEXED).unwrap();
+        let mut deps = mock_dependencies_with_balance(&coins(600, "ujuno"));
+
+        let msg = InstantiateMsg {
+            owner: Some("owner0000".to_string()),
+            cw20_token_address: "token0000".to_string(),
+        };
+        let info = mock_info("addr0000", &[]);
+        instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
         let msg = ExecuteMsg::RegisterMerkleRoot {
-            merkle_root: test_data.root,
+            merkle_root: test_data.root.clone(),
             expiration: None,
             start: None,
+            native_denom: Some("ujuno".to_string()),
+            cw20_token_address: None,
+            total_amount: Some(Uint128::new(600)),
+            vesting: None,
+            cross_chain: None,
+            decay: None,
+            tranches: None,
+            indexed_leaves: Some(true),
+            metadata: None,
         };
-        let _res = execute(deps.as_mut(), env, info, msg).unwrap();
+        execute(deps.as_mut(), mock_env(), mock_info("owner0000", &[]), msg).unwrap();
 
-        // Claim next airdrop
-        let msg = ExecuteMsg::Claim {
-            amount: test_data.amount.clone(),
-            stage: 2u8,
+        let claim = |leaf_index| ExecuteMsg::Claim {
+            stage: 1,
+            amount: test_data.amount,
             proof: test_data.proofs.clone(),
+            sig_info: None,
+            leaf_index,
         };
+        let info = mock_info(test_data.account.as_str(), &[]);
 
-        let env = mock_env();
-        let info = mock_info(test_data.account.clone().as_str(), &[]);
-        let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
-        let expected: SubMsg<_> = SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
-            contract_addr: "token0000".to_string(),
-            funds: vec![],
-            msg: to_binary(&Cw20ExecuteMsg::Transfer {
-                recipient: test_data.account.clone(),
-                amount: test_data.amount,
-            })
-            .unwrap(),
-        }));
-        assert_eq!(res.messages, vec![expected]);
+        // leaves commit to their index
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), claim(None)).unwrap_err();
+        assert_eq!(res, ContractError::LeafIndexRequired { stage: 1 });
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), claim(Some(0))).unwrap_err();
+        assert_eq!(res, ContractError::VerificationFailed {});
+
+        execute(deps.as_mut(), mock_env(), info.clone(), claim(test_data.index)).unwrap();
+        let res = execute(deps.as_mut(), mock_env(), info, claim(test_data.index)).unwrap_err();
+        assert_eq!(res, ContractError::Claimed {});
 
+        // only the claimed bit is written, not a claim record
+        let word = CLAIMED_BITS.load(&deps.storage, (1, 0)).unwrap();
+        assert_eq!(word, 1 << test_data.index.unwrap());
+        assert!(!CLAIM.has(&deps.storage, (1, &Addr::unchecked(&test_data.account))));
+        for (leaf_index, claimed) in [(test_data.index, true), (Some(0), false)] {
+            let msg = QueryMsg::IsClaimed {
+                stage: 1,
+                address: test_data.account.clone(),
+                leaf_index,
+            };
+            let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+            assert_eq!(from_binary::<IsClaimedResponse>(&res).unwrap().is_claimed, claimed);
+        }
+        let msg = QueryMsg::IsClaimed {
+            stage: 1,
+            address: test_data.account.clone(),
+            leaf_index: None,
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap_err();
         assert_eq!(
-            res.attributes,
-            vec![
-                attr("action", "claim"),
-                attr("stage", "2"),
-                attr("address", test_data.account.clone()),
-                attr("amount", test_data.amount.clone())
-            ]
+            res,
+            StdError::generic_err(ContractError::LeafIndexRequired { stage: 1 }.to_string())
         );
 
-        let query_msg = QueryMsg::AlreadyClaimed { stage: 2u8 };
-        let res = query(deps.as_ref(), env.clone(), query_msg).unwrap();
-        let val = from_binary::<AlreadyClaimedResponse>(&res).unwrap();
-        assert_eq!(val.stage, 2u8);
-        assert_eq!(val.amount, test_data.amount.clone());
-
-        // Third test - failure to claim
-        // Claim next airdrop
-        let test_data: Encoded = from_slice(TEST_DATA_2_FAILURE).unwrap();
-        let msg = ExecuteMsg::Claim {
-            amount: test_data.amount.clone(),
-            stage: 2u8,
-            proof: test_data.proofs.clone(),
+        // exclusions check the claimed bits by leaf index
+        let owner = mock_info("owner0000", &[]);
+        let exclude = |leaf_indexes| ExecuteMsg::Exclude {
+            stage: 1,
+            addresses: vec![test_data.account.clone(), "addr0001".to_string()],
+            leaf_indexes,
         };
-
-        let env = mock_env();
-        let info = mock_info(test_data.account.clone().as_str(), &[]);
-        let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap_err();
-        assert_eq!(res, ContractError::Hex { 0: FromHexError::InvalidHexCharacter { c: 'z', index: 0 } });
+        let res = execute(deps.as_mut(), mock_env(), owner.clone(), exclude(None)).unwrap_err();
+        assert_eq!(res, ContractError::LeafIndexRequired { stage: 1 });
+        let res = execute(deps.as_mut(), mock_env(), owner.clone(), exclude(Some(vec![0])));
+        assert_eq!(res.unwrap_err(), ContractError::InvalidInput {});
+        let leaf_indexes = Some(vec![test_data.index.unwrap(), 0]);
+        let res = execute(deps.as_mut(), mock_env(), owner, exclude(leaf_indexes)).unwrap();
+        assert_eq!(res.attributes[2], attr("addresses", "addr0001"));
     }
 
     #[test]
@@ -576,14 +4133,25 @@ mod tests {
                 .to_string(),
             expiration: Some(Expiration::AtHeight(100)),
             start: None,
+            native_denom: None,
+            cw20_token_address: None,
+            total_amount: Some(Uint128::new(1_000)),
+            vesting: None,
+            cross_chain: None,
+            decay: None,
+            tranches: None,
+            indexed_leaves: None,
+            metadata: None,
         };
         execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
 
         // can't claim expired
         let msg = ExecuteMsg::Claim {
             amount: Uint128::new(5),
-            stage: 1u8,
+            stage: 1u32,
             proof: vec![],
+            sig_info: None,
+            leaf_index: None,
         };
 
         let res = execute(deps.as_mut(), env, info, msg).unwrap_err();
@@ -596,6 +4164,109 @@ mod tests {
         )
     }
 
+    #[test]
+    fn update_stage() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg {
+            owner: Some("owner0000".to_string()),
+            cw20_token_address: "token0000".to_string(),
+        };
+        let env = mock_env();
+        let info = mock_info("addr0000", &[]);
+        let _res = instantiate(deps.as_mut(), env, info, msg).unwrap();
+
+        let mut env = mock_env();
+        let height = env.block.height;
+        let info = mock_info("owner0000", &[]);
+        let msg = ExecuteMsg::RegisterMerkleRoot {
+            merkle_root: "5d4f48f147cb6cb742b376dce5626b2a036f69faec10cd73631c791780e150fc"
+                .to_string(),
+            expiration: Some(Expiration::AtHeight(height + 100)),
+            start: Some(Scheduled::AtHeight(height + 10)),
+            native_denom: None,
+            cw20_token_address: None,
+            total_amount: Some(Uint128::new(1_000)),
+            vesting: None,
+            cross_chain: None,
+            decay: None,
+            tranches: None,
+            indexed_leaves: None,
+            metadata: None,
+        };
+        execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+        let update = |expiration: Option<Expiration>, start: Option<Scheduled>| {
+            ExecuteMsg::UpdateStage {
+                stage: 1,
+                expiration,
+                start,
+            }
+        };
+
+        // owner only
+        let msg = update(Some(Expiration::AtHeight(height + 200)), None);
+        let res = execute(deps.as_mut(), env.clone(), mock_info("addr0000", &[]), msg).unwrap_err();
+        assert_eq!(res, ContractError::Unauthorized {});
+
+        // expiration can't be shortened or change kind
+        for expiration in [
+            Expiration::AtHeight(height + 50),
+            Expiration::AtHeight(height + 100),
+            Expiration::AtTime(env.block.time.plus_seconds(10_000)),
+        ] {
+            let msg = update(Some(expiration), None);
+            let res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap_err();
+            assert_eq!(res, ContractError::InvalidInput {});
+        }
+
+        let msg = update(
+            Some(Expiration::AtHeight(height + 200)),
+            Some(Scheduled::AtHeight(height + 20)),
+        );
+        let res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        assert_eq!(
+            res.attributes,
+            vec![
+                attr("action", "update_stage"),
+                attr("stage", "1"),
+                attr("old_expiration", Expiration::AtHeight(height + 100).to_string()),
+                attr("new_expiration", Expiration::AtHeight(height + 200).to_string()),
+                attr("old_start", Scheduled::AtHeight(height + 10).to_string()),
+                attr("new_start", Scheduled::AtHeight(height + 20).to_string()),
+            ]
+        );
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::MerkleRoot { stage: 1 }).unwrap();
+        let merkle_root: MerkleRootResponse = from_binary(&res).unwrap();
+        assert_eq!(merkle_root.expiration, Expiration::AtHeight(height + 200));
+        assert_eq!(merkle_root.start, Some(Scheduled::AtHeight(height + 20)));
+
+        // start must be ahead and before the expiration
+        for start in [
+            Scheduled::AtHeight(height),
+            Scheduled::AtHeight(height + 200),
+            Scheduled::AtHeight(height + 250),
+        ] {
+            let msg = update(None, Some(start));
+            let res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap_err();
+            assert_eq!(res, ContractError::InvalidInput {});
+        }
+        let msg = update(None, Some(Scheduled::AtTime(env.block.time.plus_seconds(10))));
+        execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        let msg = update(None, Some(Scheduled::AtHeight(height + 20)));
+        execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+        // start is fixed once the stage began, expiration can still be extended
+        env.block.height = height + 20;
+        let msg = update(None, Some(Scheduled::AtHeight(height + 30)));
+        let res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap_err();
+        assert_eq!(res, ContractError::StageBegun { stage: 1 });
+
+        env.block.height = height + 300;
+        let msg = update(Some(Expiration::Never {}), None);
+        execute(deps.as_mut(), env, info, msg).unwrap();
+    }
+
     #[test]
     fn stage_starts() {
         let mut deps = mock_dependencies();
@@ -617,14 +4288,25 @@ mod tests {
                 .to_string(),
             expiration: None,
             start: Some(Scheduled::AtHeight(200_000)),
+            native_denom: None,
+            cw20_token_address: None,
+            total_amount: Some(Uint128::new(1_000)),
+            vesting: None,
+            cross_chain: None,
+            decay: None,
+            tranches: None,
+            indexed_leaves: None,
+            metadata: None,
         };
         execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
 
         // can't claim expired
         let msg = ExecuteMsg::Claim {
             amount: Uint128::new(5),
-            stage: 1u8,
+            stage: 1u32,
             proof: vec![],
+            sig_info: None,
+            leaf_index: None,
         };
 
         let res = execute(deps.as_mut(), env, info, msg).unwrap_err();
@@ -658,6 +4340,15 @@ mod tests {
                 .to_string(),
             expiration: None,
             start: None,
+            native_denom: None,
+            cw20_token_address: None,
+            total_amount: Some(Uint128::new(1_000)),
+            vesting: None,
+            cross_chain: None,
+            decay: None,
+            tranches: None,
+            indexed_leaves: None,
+            metadata: None,
         };
         let _res = execute(deps.as_mut(), env, info, msg).unwrap();
 
@@ -671,6 +4362,10 @@ mod tests {
         let res = execute(deps.as_mut(), env, info, msg).unwrap();
         assert_eq!(0, res.messages.len());
 
+        let env = mock_env();
+        let info = mock_info("owner0001", &[]);
+        execute(deps.as_mut(), env, info, ExecuteMsg::AcceptOwnership {}).unwrap();
+
         // freeze contract
         let env = mock_env();
         let info = mock_info("owner0001", &[]);
@@ -687,6 +4382,15 @@ mod tests {
                 .to_string(),
             expiration: None,
             start: None,
+            native_denom: None,
+            cw20_token_address: None,
+            total_amount: Some(Uint128::new(1_000)),
+            vesting: None,
+            cross_chain: None,
+            decay: None,
+            tranches: None,
+            indexed_leaves: None,
+            metadata: None,
         };
         let res = execute(deps.as_mut(), env, info, msg).unwrap_err();
         assert_eq!(res, ContractError::Unauthorized {});
@@ -699,8 +4403,301 @@ mod tests {
                 .to_string(),
             expiration: None,
             start: None,
+            native_denom: None,
+            cw20_token_address: None,
+            total_amount: Some(Uint128::new(1_000)),
+            vesting: None,
+            cross_chain: None,
+            decay: None,
+            tranches: None,
+            indexed_leaves: None,
+            metadata: None,
+        };
+        let res = execute(deps.as_mut(), env, info, msg).unwrap_err();
+        assert_eq!(res, ContractError::Unauthorized {});
+    }
+
+    #[test]
+    fn roles() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+
+        let msg = InstantiateMsg {
+            owner: Some("owner0000".to_string()),
+            cw20_token_address: "token0000".to_string(),
+        };
+        let info = mock_info("addr0000", &[]);
+        instantiate(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+        let register = ExecuteMsg::RegisterMerkleRoot {
+            merkle_root: "5d4f48f147cb6cb742b376dce5626b2a036f69faec10cd73631c791780e150fc"
+                .to_string(),
+            expiration: Some(Expiration::AtHeight(env.block.height + 10)),
+            start: None,
+            native_denom: None,
+            cw20_token_address: None,
+            total_amount: Some(Uint128::new(1_000)),
+            vesting: None,
+            cross_chain: None,
+            decay: None,
+            tranches: None,
+            indexed_leaves: None,
+            metadata: None,
+        };
+
+        // only the owner grants roles
+        let info = mock_info("ops0000", &[]);
+        let msg = ExecuteMsg::GrantRole {
+            role: Role::Registrar,
+            address: "ops0000".to_string(),
+        };
+        let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap_err();
+        assert_eq!(res, ContractError::Unauthorized {});
+
+        for (role, address) in [
+            (Role::Registrar, "ops0000"),
+            (Role::Pauser, "ops0000"),
+            (Role::Withdrawer, "treasury0000"),
+        ] {
+            let info = mock_info("owner0000", &[]);
+            let msg = ExecuteMsg::GrantRole {
+                role,
+                address: address.to_string(),
+            };
+            execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+        }
+
+        let res = query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::Roles {
+                address: "ops0000".to_string(),
+            },
+        )
+        .unwrap();
+        let roles: RolesResponse = from_binary(&res).unwrap();
+        assert_eq!(roles.roles, vec![Role::Registrar, Role::Pauser]);
+
+        let res = query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::RoleMembers {
+                role: Role::Withdrawer,
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap();
+        let members: RoleMembersResponse = from_binary(&res).unwrap();
+        assert_eq!(members.addresses, vec!["treasury0000".to_string()]);
+
+        // registrar registers and pauses, but can't withdraw or manage the config
+        let info = mock_info("ops0000", &[]);
+        execute(deps.as_mut(), env.clone(), info.clone(), register.clone()).unwrap();
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            info.clone(),
+            ExecuteMsg::PauseStage { stage: 1 },
+        )
+        .unwrap();
+        let msg = ExecuteMsg::UpdateConfig {
+            new_owner: Some("ops0000".to_string()),
+        };
+        let res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap_err();
+        assert_eq!(res, ContractError::Unauthorized {});
+
+        // nor pick a decay treasury, which receives funds
+        let mut decay_register = register.clone();
+        if let ExecuteMsg::RegisterMerkleRoot { decay, .. } = &mut decay_register {
+            *decay = Some(DecayTemplate {
+                floor: Decimal::percent(50),
+                treasury: "ops0000".to_string(),
+            });
+        }
+        let res = execute(deps.as_mut(), env.clone(), info, decay_register).unwrap_err();
+        assert_eq!(res, ContractError::Unauthorized {});
+
+        let mut expired_env = env.clone();
+        expired_env.block.height += 11;
+        let info = mock_info("ops0000", &[]);
+        let res = execute(
+            deps.as_mut(),
+            expired_env.clone(),
+            info,
+            ExecuteMsg::BurnUnclaimed { stage: 1, amount: None },
+        )
+        .unwrap_err();
+        assert_eq!(res, ContractError::Unauthorized {});
+
+        // withdrawer can't register
+        let info = mock_info("treasury0000", &[]);
+        let res = execute(deps.as_mut(), env.clone(), info.clone(), register).unwrap_err();
+        assert_eq!(res, ContractError::Unauthorized {});
+
+        // but recovers unclaimed funds
+        let msg = ExecuteMsg::WithdrawUnclaimed {
+            stage: 1,
+            recipient: "treasury0000".to_string(),
+            amount: None,
+        };
+        execute(deps.as_mut(), expired_env, info, msg).unwrap();
+
+        // revoked roles are gone
+        let info = mock_info("owner0000", &[]);
+        let msg = ExecuteMsg::RevokeRole {
+            role: Role::Pauser,
+            address: "ops0000".to_string(),
+        };
+        execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+        let info = mock_info("ops0000", &[]);
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            info,
+            ExecuteMsg::ResumeStage { stage: 1 },
+        )
+        .unwrap_err();
+        assert_eq!(res, ContractError::Unauthorized {});
+
+        // and all roles are void once frozen
+        let info = mock_info("owner0000", &[]);
+        let msg = ExecuteMsg::UpdateConfig { new_owner: None };
+        execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+        let info = mock_info("ops0000", &[]);
+        let msg = ExecuteMsg::RegisterMerkleRoot {
+            merkle_root: "ebaa83c7eaf7467c378d2f37b5e46752d904d2d17acd380b24b02e3b398b3e5a"
+                .to_string(),
+            expiration: None,
+            start: None,
+            native_denom: None,
+            cw20_token_address: None,
+            total_amount: Some(Uint128::new(1_000)),
+            vesting: None,
+            cross_chain: None,
+            decay: None,
+            tranches: None,
+            indexed_leaves: None,
+            metadata: None,
         };
         let res = execute(deps.as_mut(), env, info, msg).unwrap_err();
         assert_eq!(res, ContractError::Unauthorized {});
     }
+
+    #[test]
+    fn migrate_u8_stages() {
+        let test_data: Encoded = from_slice(TEST_DATA_1).unwrap();
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+
+        // state as written by 0.7.0
+        set_contract_version(deps.as_mut().storage, CONTRACT_NAME, "0.7.0").unwrap();
+        CONFIG
+            .save(
+                deps.as_mut().storage,
+                &Config {
+                    owner: Some(Addr::unchecked("owner0000")),
+                    cw20_token_address: Addr::unchecked("token0000"),
+                },
+            )
+            .unwrap();
+        LATEST_STAGE.save(deps.as_mut().storage, &1).unwrap();
+        let legacy_root: Map<u8, String> = Map::new(MERKLE_ROOT_PREFIX);
+        legacy_root.save(deps.as_mut().storage, 1, &test_data.root).unwrap();
+        let legacy_expiration: Map<u8, Expiration> = Map::new(STAGE_EXPIRATION_KEY);
+        legacy_expiration
+            .save(deps.as_mut().storage, 1, &Expiration::Never {})
+            .unwrap();
+        let legacy_amount: Map<u8, Uint128> = Map::new(STAGE_AMOUNT_KEY);
+        legacy_amount
+            .save(deps.as_mut().storage, 1, &Uint128::new(1_000))
+            .unwrap();
+        let legacy_funded: Map<u8, Uint128> = Map::new(STAGE_FUNDED_KEY);
+        legacy_funded
+            .save(deps.as_mut().storage, 1, &Uint128::new(1_000))
+            .unwrap();
+        let legacy_claimed: Map<u8, Uint128> = Map::new(ALREADY_CLAIMED_PREFIX);
+        legacy_claimed
+            .save(deps.as_mut().storage, 1, &Uint128::new(100))
+            .unwrap();
+        let legacy_claim: Map<(&Addr, u8), bool> = Map::new(LEGACY_CLAIM_PREFIX);
+        legacy_claim
+            .save(deps.as_mut().storage, (&Addr::unchecked("claimer0000"), 1), &true)
+            .unwrap();
+
+        migrate(deps.as_mut(), env.clone(), MigrateMsg {}).unwrap();
+        assert_eq!(
+            get_contract_version(&deps.storage).unwrap().version,
+            CONTRACT_VERSION
+        );
+
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::MerkleRoot { stage: 1 }).unwrap();
+        let merkle_root: MerkleRootResponse = from_binary(&res).unwrap();
+        assert_eq!(merkle_root.merkle_root, test_data.root);
+        assert_eq!(merkle_root.total_amount, Some(Uint128::new(1_000)));
+
+        let res = query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::IsClaimed {
+                stage: 1,
+                address: "claimer0000".to_string(),
+                leaf_index: None,
+            },
+        )
+        .unwrap();
+        assert!(from_binary::<IsClaimedResponse>(&res).unwrap().is_claimed);
+
+        // claimed amounts and times were not recorded
+        let msg = QueryMsg::ListClaims {
+            stage: 1,
+            start_after: None,
+            limit: None,
+        };
+        let res = query(deps.as_ref(), env.clone(), msg).unwrap();
+        assert_eq!(
+            from_binary::<ListClaimsResponse>(&res).unwrap().claims,
+            vec![ClaimResponse {
+                address: "claimer0000".to_string(),
+                amount: Uint128::zero(),
+                height: 0,
+                time: Timestamp::from_seconds(0),
+            }]
+        );
+
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::StageStats { stage: 1 }).unwrap();
+        let stats: StageStatsResponse = from_binary(&res).unwrap();
+        assert_eq!(stats.claimed_amount, Uint128::new(100));
+
+        // migrated stage is claimable and new stages go past 255
+        let info = mock_info(test_data.account.as_str(), &[]);
+        let msg = ExecuteMsg::Claim {
+            amount: test_data.amount,
+            stage: 1,
+            proof: test_data.proofs,
+            sig_info: None,
+            leaf_index: None,
+        };
+        execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+        LATEST_STAGE.save(deps.as_mut().storage, &255).unwrap();
+        let msg = ExecuteMsg::RegisterMerkleRoot {
+            merkle_root: test_data.root,
+            expiration: None,
+            start: None,
+            native_denom: None,
+            cw20_token_address: None,
+            total_amount: Some(Uint128::new(1_000)),
+            vesting: None,
+            cross_chain: None,
+            decay: None,
+            tranches: None,
+            indexed_leaves: None,
+            metadata: None,
+        };
+        execute(deps.as_mut(), env.clone(), mock_info("owner0000", &[]), msg).unwrap();
+        let res = query(deps.as_ref(), env, QueryMsg::LatestStage {}).unwrap();
+        let latest_stage: LatestStageResponse = from_binary(&res).unwrap();
+        assert_eq!(latest_stage.latest_stage, 256);
+    }
 }
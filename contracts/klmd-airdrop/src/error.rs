@@ -1,6 +1,7 @@
-use cosmwasm_std::StdError;
+use cosmwasm_std::{StdError, Uint128};
 use cw_utils::{Expiration, Scheduled};
 use hex::FromHexError;
+use indexable_hooks::HookError;
 use thiserror::Error;
 
 #[derive(Error, Debug, PartialEq)]
@@ -11,6 +12,9 @@ pub enum ContractError {
     #[error("{0}")]
     Hex(#[from] FromHexError),
 
+    #[error("{0}")]
+    HookError(#[from] HookError),
+
     #[error("Unauthorized")]
     Unauthorized {},
 
@@ -30,8 +34,51 @@ pub enum ContractError {
     CannotMigrate { previous_contract: String },
 
     #[error("Airdrop stage {stage} expired at {expiration}")]
-    StageExpired { stage: u8, expiration: Expiration },
+    StageExpired { stage: u32, expiration: Expiration },
 
     #[error("Airdrop stage {stage} begins at {start}")]
-    StageNotBegun { stage: u8, start: Scheduled },
+    StageNotBegun { stage: u32, start: Scheduled },
+
+    #[error("Airdrop stage {stage} has not expired yet")]
+    StageNotExpired { stage: u32 },
+
+    #[error("Airdrop stage {stage} has already begun")]
+    StageBegun { stage: u32 },
+
+    #[error("Airdrop stage {stage} is paused")]
+    StagePaused { stage: u32 },
+
+    #[error("Airdrop stage {stage} is closed")]
+    StageClosed { stage: u32 },
+
+    #[error("Airdrop stage total amount is required")]
+    MissingTotalAmount {},
+
+    #[error("Claim exceeds the total amount of airdrop stage {stage}")]
+    StageAmountExceeded { stage: u32 },
+
+    #[error("Airdrop stage {stage} is not funded enough for this claim")]
+    StageUnderfunded { stage: u32 },
+
+    #[error("Funding exceeds the total amount of airdrop stage {stage}")]
+    StageOverfunded { stage: u32 },
+
+    #[error("Airdrop stage {stage} lists external addresses, a signature is required")]
+    SignatureRequired { stage: u32 },
+
+    #[error("Address {address} is excluded from airdrop stage {stage}")]
+    AddressExcluded { stage: u32, address: String },
+
+    #[error("Airdrop stage {stage} has indexed leaves, a leaf index is required")]
+    LeafIndexRequired { stage: u32 },
+
+    #[error("Next tranche of airdrop stage {stage} requires a vote")]
+    TrancheLocked { stage: u32 },
+
+    #[error("Insufficient {denom} balance: contract holds {balance}, stage requires {required}")]
+    InsufficientFunds {
+        denom: String,
+        balance: Uint128,
+        required: Uint128,
+    },
 }
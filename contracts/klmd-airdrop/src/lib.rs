@@ -1,6 +1,7 @@
 pub mod contract;
 mod error;
 pub mod msg;
+mod signature;
 pub mod state;
 
 pub use crate::error::ContractError;
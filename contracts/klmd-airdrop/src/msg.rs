@@ -1,8 +1,12 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use cosmwasm_std::Uint128;
+use cosmwasm_std::{Binary, Decimal, Timestamp, Uint128};
+use cw20::Cw20ReceiveMsg;
 use cw_utils::{Expiration, Scheduled};
+use vote_hooks::VoteHookMsg;
+
+use crate::state::{CrossChain, Role, StageDecay, StageMetadata, StageTranches, StageVesting};
 
 #[derive(Serialize, Deserialize, JsonSchema)]
 pub struct InstantiateMsg {
@@ -13,56 +17,274 @@ pub struct InstantiateMsg {
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
+#[allow(clippy::large_enum_variant)]
 pub enum ExecuteMsg {
     UpdateConfig {
-        /// NewOwner if non sent, contract gets locked. Recipients can receive airdrops
-        /// but owner cannot register new stages.
+        /// NewOwner is proposed and takes over once accepting. If non sent, contract gets
+        /// locked right away. Recipients can receive airdrops but owner cannot register new
+        /// stages.
         new_owner: Option<String>,
     },
+    /// AcceptOwnership completes a handover, sent by the proposed owner.
+    AcceptOwnership {},
+    /// GrantRole lets address act on behalf of the owner for the role.
+    GrantRole { role: Role, address: String },
+    RevokeRole { role: Role, address: String },
     RegisterMerkleRoot {
         /// MerkleRoot is hex-encoded merkle root.
         merkle_root: String,
         expiration: Option<Expiration>,
         start: Option<Scheduled>,
+        /// NativeDenom if set, stage is paid out in this native coin instead of the cw20 token.
+        native_denom: Option<String>,
+        /// Cw20TokenAddress if set, stage is paid out in this cw20 token instead of the config
+        /// one.
+        cw20_token_address: Option<String>,
+        /// TotalAmount distributed by the stage. Native stages are funded on registration, the
+        /// contract must already hold this much of the denom. Cw20 stages are funded afterwards
+        /// with a `FundStage` receive message. Cw20 stages without it are not tracked, claims
+        /// then rely on the contract balance as before totals existed.
+        total_amount: Option<Uint128>,
+        /// Vesting if set, claims are partly registered as vesting accounts in a
+        /// klmd-custom-vesting contract instead of being transferred.
+        vesting: Option<VestingTemplate>,
+        /// CrossChain if set, the tree lists addresses of another chain. They are claimed with a
+        /// signature of the recipient address, see `SignatureInfo`.
+        cross_chain: Option<CrossChain>,
+        /// Decay if set, claims pay out less the later they are made. Requires start and
+        /// expiration, both heights or both times, and the withdrawer role.
+        decay: Option<DecayTemplate>,
+        /// Tranches if set, later parts of each allocation are only paid once the recipient
+        /// voted in the DAO. Can't be combined with vesting.
+        tranches: Option<TranchesTemplate>,
+        /// IndexedLeaves if set, merkle leaves are `sha256(index:address amount)` with the
+        /// leaf index, and claimed flags are stored in a bitmap. Can't be combined with
+        /// tranches.
+        indexed_leaves: Option<bool>,
+        /// Metadata describes the stage, returned by the `merkle_root` and `list_stages`
+        /// queries.
+        metadata: Option<StageMetadata>,
     },
-    /// Claim does not check if contract has enough funds, owner must ensure it.
+    /// Receive funds cw20 stages, see `ReceiveMsg`.
+    Receive(Cw20ReceiveMsg),
+    /// Claim fails if it would exceed the stage's total or funded amount.
     Claim {
-        stage: u8,
+        stage: u32,
         amount: Uint128,
         /// Proof is hex-encoded merkle proof.
         proof: Vec<String>,
+        /// SigInfo is required on cross chain stages, the claim is paid to the sender.
+        sig_info: Option<SignatureInfo>,
+        /// LeafIndex is required on stages with indexed leaves.
+        leaf_index: Option<u64>,
+    },
+    /// ClaimMany claims several stages at once, paying direct payouts in one transfer per
+    /// token. Already claimed stages are skipped if skip_claimed is set, otherwise they fail
+    /// the whole batch.
+    ClaimMany {
+        claims: Vec<StageClaim>,
+        skip_claimed: bool,
+    },
+    /// PauseStage stops claims on a stage until it is resumed.
+    PauseStage { stage: u32 },
+    ResumeStage { stage: u32 },
+    /// UpdateStage extends the stage's expiration, or moves its start while it has not begun.
+    UpdateStage {
+        stage: u32,
+        expiration: Option<Expiration>,
+        start: Option<Scheduled>,
     },
+    /// WithdrawUnclaimed sends what is left of an expired stage to recipient and closes it.
+    /// Stages registered without a total amount need the amount left, up to the contract balance.
+    WithdrawUnclaimed {
+        stage: u32,
+        recipient: String,
+        amount: Option<Uint128>,
+    },
+    /// BurnUnclaimed burns what is left of an expired stage and closes it, `amount` as for
+    /// WithdrawUnclaimed.
+    BurnUnclaimed { stage: u32, amount: Option<Uint128> },
+    /// Exclude blocks addresses from claiming the stage, e.g. a sybil cluster found after the
+    /// root was registered. Their allocations are recovered with the unclaimed funds. Stages
+    /// with indexed leaves take the addresses' leaf indexes, in the same order.
+    Exclude {
+        stage: u32,
+        addresses: Vec<String>,
+        leaf_indexes: Option<Vec<u64>>,
+    },
+    /// AddClaimHook registers a contract to be sent a `ClaimHookExecuteMsg` on every claim.
+    /// Hooks that fail are removed.
+    AddClaimHook { address: String },
+    RemoveClaimHook { address: String },
+    /// VoteHook records votes reported by the proposal modules of tranche stages.
+    VoteHook(VoteHookMsg),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct StageClaim {
+    pub stage: u32,
+    pub amount: Uint128,
+    /// Proof is hex-encoded merkle proof.
+    pub proof: Vec<String>,
+    pub sig_info: Option<SignatureInfo>,
+    pub leaf_index: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct DecayTemplate {
+    /// Floor is the part of a claim still paid out at expiration. The paid out part goes down
+    /// linearly from all of it at the stage start to the floor.
+    pub floor: Decimal,
+    /// Treasury receives the decayed part of claims, e.g. the DAO.
+    pub treasury: String,
+}
+
+/// ClaimHookExecuteMsg is sent to claim hooks by the airdrop.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ClaimHookExecuteMsg {
+    ClaimHook(ClaimHookMsg),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ClaimHookMsg {
+    pub stage: u32,
+    /// Address the claim was paid to.
+    pub address: String,
+    /// Amount claimed after decay, including the vesting part on vesting stages.
+    pub amount: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TranchesTemplate {
+    /// ProposalModule is a klmd-custom-proposal contract with this contract added as vote hook.
+    pub proposal_module: String,
+    /// Tranches are the parts of each allocation, at least two summing up to 1. The first is
+    /// paid on claim, every vote on another proposal unlocks the next one.
+    pub tranches: Vec<Decimal>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SignatureInfo {
+    /// ExternalAddress is the address listed in the merkle tree.
+    pub external_address: String,
+    /// PubKey is the compressed secp256k1 key of a cosmos address. EVM keys are recovered from
+    /// the signature.
+    pub pub_key: Option<Binary>,
+    /// Signature of the sender address, an ADR-36 signature on cosmos chains and an EIP-191
+    /// `personal_sign` signature on EVM chains.
+    pub signature: Binary,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct VestingTemplate {
+    /// VestingContract is a klmd-custom-vesting contract owned by this contract, vesting the
+    /// stage token.
+    pub vesting_contract: String,
+    /// Cliff in seconds after the claim before anything vests, the amount vested since the claim
+    /// unlocks at it.
+    pub cliff: u64,
+    /// Duration in seconds of the linear vesting from the claim, at least the cliff.
+    pub duration: u64,
+    /// LiquidPercent of each claim is transferred right away, the rest vests.
+    pub liquid_percent: Decimal,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReceiveMsg {
+    /// FundStage adds the sent cw20 tokens to the stage's funded amount.
+    FundStage { stage: u32 },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
     Config {},
-    MerkleRoot { stage: u8 },
+    MerkleRoot { stage: u32 },
     LatestStage {},
-    IsClaimed { stage: u8, address: String },
-    AlreadyClaimed { stage: u8 },
+    /// IsClaimed requires the leaf index on stages with indexed leaves.
+    IsClaimed {
+        stage: u32,
+        address: String,
+        leaf_index: Option<u64>,
+    },
+    AlreadyClaimed { stage: u32 },
+    StageStats { stage: u32 },
+    /// VerifyClaim checks a claim without executing it. On cross chain stages address is the
+    /// external address.
+    VerifyClaim {
+        stage: u32,
+        address: String,
+        amount: Uint128,
+        /// Proof is hex-encoded merkle proof.
+        proof: Vec<String>,
+        leaf_index: Option<u64>,
+        /// Voter is the address whose votes unlock tranches, the one a claim pays out to.
+        /// Defaults to address, cross chain tranche stages need the claiming address.
+        voter: Option<String>,
+    },
+    /// ListClaims lists who claimed the stage, ordered by address. Claims of stages with
+    /// indexed leaves are not recorded per address and not listed.
+    ListClaims {
+        stage: u32,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    ClaimHooks {},
+    /// Roles returns the roles granted to address.
+    Roles { address: String },
+    /// RoleMembers lists the addresses granted role, ordered by address.
+    RoleMembers {
+        role: Role,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// ListStages lists stages with their metadata and status, ordered by stage.
+    ListStages {
+        start_after: Option<u32>,
+        limit: Option<u32>,
+    },
+    /// Excluded lists the addresses excluded from the stage, ordered by address.
+    Excluded {
+        stage: u32,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
 #[serde(rename_all = "snake_case")]
 pub struct ConfigResponse {
     pub owner: Option<String>,
+    /// PendingOwner was proposed by the owner and has not accepted yet.
+    pub pending_owner: Option<String>,
     pub cw20_token_address: String,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct MerkleRootResponse {
-    pub stage: u8,
+    pub stage: u32,
     /// MerkleRoot is hex-encoded merkle root.
     pub merkle_root: String,
     pub expiration: Expiration,
     pub start: Option<Scheduled>,
+    pub native_denom: Option<String>,
+    /// Cw20TokenAddress the stage is paid out in, None for native stages.
+    pub cw20_token_address: Option<String>,
+    pub total_amount: Option<Uint128>,
+    pub paused: bool,
+    pub vesting: Option<StageVesting>,
+    pub cross_chain: Option<CrossChain>,
+    pub decay: Option<StageDecay>,
+    pub tranches: Option<StageTranches>,
+    pub indexed_leaves: bool,
+    pub metadata: Option<StageMetadata>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct LatestStageResponse {
-    pub latest_stage: u8,
+    pub latest_stage: u32,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -70,11 +292,91 @@ pub struct IsClaimedResponse {
     pub is_claimed: bool,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct VerifyClaimResponse {
+    pub proof_valid: bool,
+    pub started: bool,
+    pub expired: bool,
+    /// Claimed is set once nothing is left to claim, on tranche stages once every tranche is paid.
+    pub claimed: bool,
+    pub excluded: bool,
+    /// ClaimableAmount is the part of amount a claim would pay out now.
+    pub claimable_amount: Uint128,
+    /// TranchesPaid and TranchesUnlocked count the tranches of tranche stages paid out and
+    /// unlocked by the voter's votes.
+    pub tranches_paid: Option<u32>,
+    pub tranches_unlocked: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ClaimResponse {
+    pub address: String,
+    pub amount: Uint128,
+    pub height: u64,
+    pub time: Timestamp,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ListClaimsResponse {
+    pub claims: Vec<ClaimResponse>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum StageStatus {
+    /// Pending stages have not begun yet.
+    Pending,
+    Active,
+    Expired,
+    /// Closed stages had their unclaimed funds withdrawn or burned.
+    Closed,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct StageResponse {
+    pub stage: u32,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub total_leaves: Option<u64>,
+    pub claimed_amount: Uint128,
+    pub status: StageStatus,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ListStagesResponse {
+    pub stages: Vec<StageResponse>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ExcludedResponse {
+    pub addresses: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct RolesResponse {
+    pub roles: Vec<Role>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct RoleMembersResponse {
+    pub addresses: Vec<String>,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct AlreadyClaimedResponse {
-    pub stage: u8,
+    pub stage: u32,
     pub amount: Uint128,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct StageStatsResponse {
+    pub stage: u32,
+    pub total_amount: Uint128,
+    pub funded_amount: Uint128,
+    pub claimed_amount: Uint128,
+    /// RemainingAmount is the funded amount not claimed yet.
+    pub remaining_amount: Uint128,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct MigrateMsg {}
@@ -0,0 +1,105 @@
+use bech32::{ToBase32, Variant};
+use cosmwasm_std::{Addr, Api, Binary};
+use ripemd160::Ripemd160;
+use sha2::{Digest, Sha256};
+use sha3::Keccak256;
+
+use crate::error::ContractError;
+use crate::msg::SignatureInfo;
+use crate::state::CrossChain;
+
+/// Verifies that the owner of `sig_info.external_address` signed the recipient's address, so
+/// the allocation of the external address can be claimed by the recipient.
+pub fn verify_external_address(
+    api: &dyn Api,
+    chain: &CrossChain,
+    sig_info: &SignatureInfo,
+    recipient: &Addr,
+) -> Result<(), ContractError> {
+    match chain {
+        CrossChain::Cosmos { hrp } => verify_cosmos(api, hrp, sig_info, recipient),
+        CrossChain::Evm {} => verify_evm(api, sig_info, recipient),
+    }
+}
+
+/// Returns the external address as listed in merkle trees. EVM addresses are case insensitive and
+/// listed in lowercase, so checksummed forms claim the same allocation.
+pub fn tree_external_address(chain: &CrossChain, address: &str) -> String {
+    match chain {
+        CrossChain::Cosmos { .. } => address.to_string(),
+        CrossChain::Evm {} => address.to_lowercase(),
+    }
+}
+
+/// Cosmos signatures are ADR-36 `sign/MsgSignData` signatures as produced by Keplr's
+/// `signArbitrary`, with the recipient address as data.
+fn verify_cosmos(
+    api: &dyn Api,
+    hrp: &str,
+    sig_info: &SignatureInfo,
+    recipient: &Addr,
+) -> Result<(), ContractError> {
+    let pub_key = sig_info
+        .pub_key
+        .as_ref()
+        .ok_or(ContractError::VerificationFailed {})?;
+
+    let address = bech32::encode(
+        hrp,
+        Ripemd160::digest(&Sha256::digest(pub_key)).to_base32(),
+        Variant::Bech32,
+    )
+    .map_err(|_| ContractError::VerificationFailed {})?;
+    if address != sig_info.external_address {
+        return Err(ContractError::VerificationFailed {});
+    }
+
+    let doc = format!(
+        r#"{{"account_number":"0","chain_id":"","fee":{{"amount":[],"gas":"0"}},"memo":"","msgs":[{{"type":"sign/MsgSignData","value":{{"data":"{}","signer":"{}"}}}}],"sequence":"0"}}"#,
+        Binary::from(recipient.as_bytes()).to_base64(),
+        address
+    );
+    let hash = Sha256::digest(doc.as_bytes());
+    let valid = api
+        .secp256k1_verify(&hash, &sig_info.signature, pub_key)
+        .map_err(|_| ContractError::VerificationFailed {})?;
+    if !valid {
+        return Err(ContractError::VerificationFailed {});
+    }
+    Ok(())
+}
+
+/// EVM signatures are EIP-191 `personal_sign` signatures of the recipient address, with the
+/// recovery id as last byte.
+fn verify_evm(
+    api: &dyn Api,
+    sig_info: &SignatureInfo,
+    recipient: &Addr,
+) -> Result<(), ContractError> {
+    let signature = sig_info.signature.as_slice();
+    if signature.len() != 65 {
+        return Err(ContractError::VerificationFailed {});
+    }
+    let recovery_param = match signature[64] {
+        0 | 27 => 0,
+        1 | 28 => 1,
+        _ => return Err(ContractError::VerificationFailed {}),
+    };
+
+    let message = format!(
+        "\x19Ethereum Signed Message:\n{}{}",
+        recipient.as_str().len(),
+        recipient
+    );
+    let hash = Keccak256::digest(message.as_bytes());
+    let pub_key = api
+        .secp256k1_recover_pubkey(&hash, &signature[..64], recovery_param)
+        .map_err(|_| ContractError::VerificationFailed {})?;
+
+    // the address is the last 20 bytes of the hashed uncompressed key, without its prefix
+    let address = format!("0x{}", hex::encode(&Keccak256::digest(&pub_key[1..])[12..]));
+    if address != sig_info.external_address.to_lowercase() {
+        return Err(ContractError::VerificationFailed {});
+    }
+    Ok(())
+}
@@ -1,9 +1,10 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use cosmwasm_std::{Addr, Uint128};
+use cosmwasm_std::{Addr, Decimal, Timestamp, Uint128};
 use cw_storage_plus::{Item, Map};
 use cw_utils::{Expiration, Scheduled};
+use indexable_hooks::Hooks;
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct Config {
@@ -12,23 +13,186 @@ pub struct Config {
     pub cw20_token_address: Addr,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct StageVesting {
+    pub vesting_contract: Addr,
+    /// Cliff in seconds after the claim before anything vests, the amount vested since the claim
+    /// unlocks at it.
+    pub cliff: u64,
+    /// Duration in seconds of the linear vesting from the claim, at least the cliff.
+    pub duration: u64,
+    pub liquid_percent: Decimal,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct StageDecay {
+    /// Floor is the part of a claim still paid out at expiration.
+    pub floor: Decimal,
+    /// Treasury receives the decayed part of claims.
+    pub treasury: Addr,
+}
+
+/// StageTranches split every allocation of a stage. The first tranche is paid on the first
+/// claim, each later one once the recipient voted on another proposal of `proposal_module`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct StageTranches {
+    pub proposal_module: Addr,
+    /// Tranches are the parts of an allocation, summing up to 1.
+    pub tranches: Vec<Decimal>,
+    /// SinceHeight is the height the stage was registered at, only votes cast after it count.
+    #[serde(default)]
+    pub since_height: u64,
+}
+
+/// StageMetadata describes a stage to frontends, it is not used by the contract.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct StageMetadata {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    /// TotalLeaves is the number of accounts in the stage's merkle tree.
+    pub total_leaves: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ClaimRecord {
+    pub amount: Uint128,
+    /// Height and time of the block the claim was made in.
+    pub height: u64,
+    pub time: Timestamp,
+}
+
+/// CrossChain stages list addresses of another chain, claimed by signing the recipient address
+/// with the listed address' key.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CrossChain {
+    /// Cosmos SDK chain addresses with the given bech32 prefix.
+    Cosmos { hrp: String },
+    /// Ethereum style hex addresses, listed in lowercase.
+    Evm {},
+}
+
+/// Role lets an address act on behalf of the owner for part of the owner's messages.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    /// Registrar registers merkle roots.
+    Registrar,
+    /// Pauser pauses and resumes stages.
+    Pauser,
+    /// Withdrawer withdraws or burns the unclaimed funds of expired stages.
+    Withdrawer,
+}
+
+impl Role {
+    pub fn key(&self) -> &'static str {
+        match self {
+            Role::Registrar => "registrar",
+            Role::Pauser => "pauser",
+            Role::Withdrawer => "withdrawer",
+        }
+    }
+}
+
 pub const CONFIG_KEY: &str = "config";
 pub const CONFIG: Item<Config> = Item::new(CONFIG_KEY);
 
+/// PendingOwner is the proposed owner until it accepts the ownership.
+pub const PENDING_OWNER_KEY: &str = "pending_owner";
+pub const PENDING_OWNER: Item<Addr> = Item::new(PENDING_OWNER_KEY);
+
+/// Roles granted per role key and address.
+pub const ROLES_PREFIX: &str = "roles";
+pub const ROLES: Map<(&str, &Addr), bool> = Map::new(ROLES_PREFIX);
+
+/// ClaimHooks are contracts notified of every claim.
+pub const CLAIM_HOOKS: Hooks = Hooks::new("claim_hooks");
+
+/// ClaimHookReplies are the hooks by reply id of their last claim message, to remove those that
+/// fail.
+pub const CLAIM_HOOK_REPLIES_PREFIX: &str = "claim_hook_replies";
+pub const CLAIM_HOOK_REPLIES: Map<u64, Addr> = Map::new(CLAIM_HOOK_REPLIES_PREFIX);
+
 pub const LATEST_STAGE_KEY: &str = "stage";
-pub const LATEST_STAGE: Item<u8> = Item::new(LATEST_STAGE_KEY);
+pub const LATEST_STAGE: Item<u32> = Item::new(LATEST_STAGE_KEY);
 
 pub const STAGE_EXPIRATION_KEY: &str = "stage_exp";
-pub const STAGE_EXPIRATION: Map<u8, Expiration> = Map::new(STAGE_EXPIRATION_KEY);
+pub const STAGE_EXPIRATION: Map<u32, Expiration> = Map::new(STAGE_EXPIRATION_KEY);
 
 pub const STAGE_START_KEY: &str = "stage_start";
-pub const STAGE_START: Map<u8, Scheduled> = Map::new(STAGE_START_KEY);
+pub const STAGE_START: Map<u32, Scheduled> = Map::new(STAGE_START_KEY);
+
+pub const STAGE_NATIVE_DENOM_KEY: &str = "stage_native_denom";
+pub const STAGE_NATIVE_DENOM: Map<u32, String> = Map::new(STAGE_NATIVE_DENOM_KEY);
+
+pub const STAGE_TOKEN_KEY: &str = "stage_token";
+pub const STAGE_TOKEN: Map<u32, Addr> = Map::new(STAGE_TOKEN_KEY);
+
+pub const STAGE_AMOUNT_KEY: &str = "stage_amount";
+pub const STAGE_AMOUNT: Map<u32, Uint128> = Map::new(STAGE_AMOUNT_KEY);
+
+pub const STAGE_FUNDED_KEY: &str = "stage_funded";
+pub const STAGE_FUNDED: Map<u32, Uint128> = Map::new(STAGE_FUNDED_KEY);
+
+pub const STAGE_VESTING_KEY: &str = "stage_vesting";
+pub const STAGE_VESTING: Map<u32, StageVesting> = Map::new(STAGE_VESTING_KEY);
+
+pub const STAGE_PAUSED_KEY: &str = "stage_paused";
+pub const STAGE_PAUSED: Map<u32, bool> = Map::new(STAGE_PAUSED_KEY);
+
+pub const STAGE_CLOSED_KEY: &str = "stage_closed";
+pub const STAGE_CLOSED: Map<u32, bool> = Map::new(STAGE_CLOSED_KEY);
+
+pub const STAGE_CROSS_CHAIN_KEY: &str = "stage_cross_chain";
+pub const STAGE_CROSS_CHAIN: Map<u32, CrossChain> = Map::new(STAGE_CROSS_CHAIN_KEY);
+
+pub const STAGE_DECAY_KEY: &str = "stage_decay";
+pub const STAGE_DECAY: Map<u32, StageDecay> = Map::new(STAGE_DECAY_KEY);
+
+pub const STAGE_TRANCHES_KEY: &str = "stage_tranches";
+pub const STAGE_TRANCHES: Map<u32, StageTranches> = Map::new(STAGE_TRANCHES_KEY);
+
+/// TranchesPaid is the number of tranches paid out per stage and claimant.
+pub const TRANCHES_PAID_PREFIX: &str = "tranches_paid";
+pub const TRANCHES_PAID: Map<(u32, &Addr), u32> = Map::new(TRANCHES_PAID_PREFIX);
+
+/// VoteModules are the proposal modules of tranche stages, accepted as vote hook senders.
+pub const VOTE_MODULES_PREFIX: &str = "vote_modules";
+pub const VOTE_MODULES: Map<&Addr, bool> = Map::new(VOTE_MODULES_PREFIX);
+
+/// Votes recorded per proposal module, voter and proposal id.
+pub const VOTES_PREFIX: &str = "votes";
+pub const VOTES: Map<(&Addr, &Addr, u64), bool> = Map::new(VOTES_PREFIX);
+
+/// VoteHeights is the number of proposals of a proposal module a voter first voted on per height.
+pub const VOTE_HEIGHTS_PREFIX: &str = "vote_heights";
+pub const VOTE_HEIGHTS: Map<(&Addr, &Addr, u64), u64> = Map::new(VOTE_HEIGHTS_PREFIX);
+
+/// StageIndexed stages have leaves committing to a leaf index, their claimed flags are kept in
+/// `CLAIMED_BITS` instead of `CLAIM`.
+pub const STAGE_INDEXED_KEY: &str = "stage_indexed";
+pub const STAGE_INDEXED: Map<u32, bool> = Map::new(STAGE_INDEXED_KEY);
+
+pub const STAGE_METADATA_KEY: &str = "stage_metadata";
+pub const STAGE_METADATA: Map<u32, StageMetadata> = Map::new(STAGE_METADATA_KEY);
 
 pub const MERKLE_ROOT_PREFIX: &str = "merkle_root";
-pub const MERKLE_ROOT: Map<u8, String> = Map::new(MERKLE_ROOT_PREFIX);
+pub const MERKLE_ROOT: Map<u32, String> = Map::new(MERKLE_ROOT_PREFIX);
+
+/// LegacyClaimPrefix held claim flags keyed by address and stage before 0.9.0.
+pub const LEGACY_CLAIM_PREFIX: &str = "claim";
+
+pub const CLAIM_PREFIX: &str = "claims";
+pub const CLAIM: Map<(u32, &Addr), ClaimRecord> = Map::new(CLAIM_PREFIX);
+
+/// ClaimedBits are the claimed flags of indexed stages, packed into u64 words keyed by stage and
+/// word index. Leaf index i is bit i % 64 of word i / 64.
+pub const CLAIMED_BITS_PREFIX: &str = "claimed_bits";
+pub const CLAIMED_BITS: Map<(u32, u64), u64> = Map::new(CLAIMED_BITS_PREFIX);
 
-pub const CLAIM_PREFIX: &str = "claim";
-pub const CLAIM: Map<(&Addr, u8), bool> = Map::new(CLAIM_PREFIX);
+/// Excluded addresses can't claim the stage, their allocations stay with it.
+pub const EXCLUDED_PREFIX: &str = "excluded";
+pub const EXCLUDED: Map<(u32, &Addr), bool> = Map::new(EXCLUDED_PREFIX);
 
 pub const ALREADY_CLAIMED_PREFIX: &str = "already_claimed";
-pub const ALREADY_CLAIMED: Map<u8, Uint128> = Map::new(ALREADY_CLAIMED_PREFIX);
+pub const ALREADY_CLAIMED: Map<u32, Uint128> = Map::new(ALREADY_CLAIMED_PREFIX);
@@ -3,7 +3,7 @@ use std::fs::create_dir_all;
 
 use cosmwasm_schema::{export_schema, remove_schemas, schema_for};
 
-use klmd_custom_vesting::msg::{ExecuteMsg, InstantiateMsg, OwnerAddressResponse, QueryMsg, TokenAddressResponse, VestingAccountResponse};
+use klmd_custom_vesting::msg::{ExecuteMsg, InstantiateMsg, OwnerAddressResponse, QueryMsg, ReceiveMsg, TokenAddressResponse, UnallocatedResponse, VestingAccountResponse, VestingSchedulesResponse};
 
 fn main() {
     let mut out_dir = current_dir().unwrap();
@@ -17,4 +17,7 @@ fn main() {
     export_schema(&schema_for!(OwnerAddressResponse), &out_dir);
     export_schema(&schema_for!(VestingAccountResponse), &out_dir);
     export_schema(&schema_for!(TokenAddressResponse), &out_dir);
+    export_schema(&schema_for!(ReceiveMsg), &out_dir);
+    export_schema(&schema_for!(UnallocatedResponse), &out_dir);
+    export_schema(&schema_for!(VestingSchedulesResponse), &out_dir);
 }
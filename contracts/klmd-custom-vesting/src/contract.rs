@@ -1,15 +1,18 @@
-use cosmwasm_std::{Addr, attr, Binary, Deps, DepsMut, Env, MessageInfo, Order, Response, StdError, StdResult, Storage, Timestamp, to_binary, Uint128, Uint64, WasmMsg};
+use cosmwasm_std::{Addr, attr, Binary, BlockInfo, Deps, DepsMut, Env, from_binary, MessageInfo, Order, Response, StdError, StdResult, Storage, Timestamp, to_binary, Uint128, Uint64, WasmMsg};
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
-use cw20::Cw20ExecuteMsg;
+use cw20::{BalanceResponse, Cw20ExecuteMsg, Cw20QueryMsg, Cw20ReceiveMsg};
 use cw20_base::ContractError;
 use cw2::set_contract_version;
+use cw_storage_plus::PrefixBound;
 
-use crate::{msg::{ExecuteMsg, InstantiateMsg, OwnerAddressResponse, QueryMsg, TokenAddressResponse, VestingAccountResponse, VestingTotalResponse}, state::{Account, ACCOUNTS, get_vesting_data_from_account, OWNER_ADDRESS, TOKEN_ADDRESS, TotalVestingInfo, VESTING_DATA, VESTING_TOTAL, VestingData}};
-use crate::msg::{InfoResponse, MigrateMsg};
+use crate::{msg::{ExecuteMsg, InstantiateMsg, OwnerAddressResponse, QueryMsg, TokenAddressResponse, VestingAccountResponse, VestingSchedule, VestingSchedulesResponse, VestingTotalResponse}, state::{Account, ACCOUNTS, BREAKPOINTS, get_vesting_data_from_account, LEGACY_ACCOUNTS, LEGACY_VESTING_DATA, LEGACY_VESTING_TOTAL, MIGRATION_HEIGHT, NEXT_SCHEDULE_ID, OWNER_ADDRESS, TOKEN_ADDRESS, TotalVestingInfo, UNALLOCATED, VESTING_TOTALS, VestingData, VestingTotals}};
+use crate::msg::{InfoResponse, MigrateMsg, ReceiveMsg, UnallocatedResponse};
 
 pub(crate) const CONTRACT_NAME: &str = "crates.io:klmd-custom-vesting";
 pub(crate) const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
+// breakpoints moved in the totals per execution, bounds the work of every write
+pub(crate) const MAX_BREAKPOINTS: usize = 100;
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
@@ -24,14 +27,12 @@ pub fn instantiate(
 
     let token_address = msg.token_address;
     TOKEN_ADDRESS.save(deps.storage, &token_address)?;
-    let vesting_info = TotalVestingInfo {
-        prevesting_amount: Uint128::zero(),
-        prevested_amount: Uint128::zero(),
-        vesting_amount: Uint128::zero(),
-        vested_amount: Uint128::zero(),
-        claimed_amount: Uint128::zero(),
+    UNALLOCATED.save(deps.storage, &Uint128::zero())?;
+    let totals = VestingTotals {
+        time: _env.block.time,
+        ..VestingTotals::default()
     };
-    VESTING_TOTAL.save(deps.storage, &vesting_info, _env.block.height)?;
+    VESTING_TOTALS.save(deps.storage, &totals, _env.block.height)?;
     Ok(Response::new().add_attribute("owner_address", owner_address).add_attribute("token_address", token_address))
 }
 
@@ -43,19 +44,60 @@ pub fn execute(deps: DepsMut, env: Env, info: MessageInfo, msg: ExecuteMsg) -> S
         },
         ExecuteMsg::DeregisterVestingAccount {
             address,
+            schedule_id,
             vested_token_recipient,
             left_vesting_token_recipient
-        } => deregister_vesting_account(deps, env, info, address, vested_token_recipient, left_vesting_token_recipient),
+        } => deregister_vesting_account(deps, env, info, address, schedule_id, vested_token_recipient, left_vesting_token_recipient),
         ExecuteMsg::RegisterVestingAccount {
             address,
             start_time,
             end_time,
+            cliff_time,
             vesting_amount,
             prevesting_amount,
-        } =>  register_vesting_account(deps, env, info, address, start_time, end_time, vesting_amount, prevesting_amount),
+        } =>  register_vesting_account(deps, env, info, address, start_time, end_time, cliff_time, vesting_amount, prevesting_amount),
         ExecuteMsg::Claim {recipient} => claim(deps, env, info, recipient),
+        ExecuteMsg::WithdrawUnallocated { amount, recipient } => withdraw_unallocated(deps, env, info, amount, recipient),
         ExecuteMsg::Snapshot {} => snapshot(deps, env, info),
         ExecuteMsg::ProposalHook(_) => snapshot(deps, env, info),
+        ExecuteMsg::Receive(msg) => receive(deps, env, info, msg),
+    }
+}
+
+fn receive(deps: DepsMut, env: Env, info: MessageInfo, wrapper: Cw20ReceiveMsg) -> StdResult<Response> {
+    let token_address = TOKEN_ADDRESS.load(deps.storage)?;
+    if info.sender != token_address {
+        return Err(StdError::generic_err("unauthorized"));
+    }
+
+    let unallocated = UNALLOCATED.may_load(deps.storage)?.unwrap_or_default();
+    let unallocated = unallocated.checked_add(wrapper.amount)?;
+
+    match from_binary(&wrapper.msg)? {
+        ReceiveMsg::Fund {} => {
+            UNALLOCATED.save(deps.storage, &unallocated)?;
+            Ok(Response::new()
+                .add_attribute("action", "fund")
+                .add_attribute("amount", wrapper.amount)
+            )
+        },
+        ReceiveMsg::RegisterVestingAccount {
+            address,
+            start_time,
+            end_time,
+            cliff_time,
+            vesting_amount,
+            prevesting_amount,
+        } => {
+            // registration is on behalf of the cw20 sender
+            let info = MessageInfo {
+                sender: deps.api.addr_validate(&wrapper.sender)?,
+                funds: vec![],
+            };
+            only_owner(deps.storage, info.sender.clone())?;
+            UNALLOCATED.save(deps.storage, &unallocated)?;
+            register_vesting_account(deps, env, info, address, start_time, end_time, cliff_time, vesting_amount, prevesting_amount)
+        },
     }
 }
 
@@ -68,30 +110,89 @@ fn only_owner(storage: &dyn Storage, sender: Addr) -> StdResult<()> {
     Ok(())
 }
 
+// records the totals at the block time for queries at later heights. Anyone can call it to
+// process breakpoints that passed in bounded steps, executes also do so on their way.
 fn snapshot(deps: DepsMut, _env: Env, _info: MessageInfo) -> StdResult<Response> {
-    let accounts_addr: Vec<Addr> = ACCOUNTS
-        .keys(deps.storage, None, None, Order::Ascending)
-        .map(|item| item.map(Into::into))
-        .collect::<StdResult<_>>()?;
+    let totals = load_totals(deps.storage, &_env)?;
+    save_totals(deps.storage, &_env, &totals)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "snapshot")
+        .add_attribute("height", Uint64::new(_env.block.height))
+        .add_attribute("time", totals.time.to_string())
+    )
+}
+
+// applies the curve changes of schedules with breakpoints up to `time` to the totals and returns
+// their keys. Breakpoints at the same time are applied together, when more than `limit` are due
+// the totals stop right before the first time left.
+fn apply_breakpoints(storage: &dyn Storage, totals: &mut VestingTotals, time: Timestamp, limit: Option<usize>) -> StdResult<Vec<(u64, (Addr, u64))>> {
+    let mut breakpoints: Vec<(u64, (Addr, u64))> = vec![];
+    let mut reached = time;
+    for item in BREAKPOINTS.prefix_range(storage, None, Some(PrefixBound::inclusive(time.nanos())), Order::Ascending) {
+        let (nanos, key) = item?.0;
+        if matches!(limit, Some(limit) if breakpoints.len() >= limit) && breakpoints.last().map(|(last, _)| *last) != Some(nanos) {
+            reached = Timestamp::from_nanos(nanos - 1);
+            break;
+        }
+        breakpoints.push((nanos, key));
+    }
 
-    let account_vest_data: Vec<(Addr, VestingData)> = accounts_addr
-        .into_iter()
-        .map(| addr | ACCOUNTS.load(deps.storage, &addr).unwrap())
-        .map(| account| (account.clone().address, get_vesting_data_from_account(account.clone(), &_env.block).unwrap()))
-        .collect();
+    for (nanos, (address, schedule_id)) in breakpoints.iter() {
+        let account = ACCOUNTS.load(storage, (address, *schedule_id))?;
+        let (prevested, vested) = account.curves(Timestamp::from_nanos(nanos - 1))?;
+        totals.prevested.sub(&prevested);
+        totals.vested.sub(&vested);
+        let (prevested, vested) = account.curves(Timestamp::from_nanos(*nanos))?;
+        totals.prevested.add(&prevested);
+        totals.vested.add(&vested);
+    }
+    totals.time = reached;
 
-    let height = _env.block.clone().height - 1;
+    Ok(breakpoints)
+}
 
-    for (addr, account_data) in account_vest_data.iter() {
-        VESTING_DATA.save(deps.storage, &addr, &account_data, height)?;
+// brings the totals towards the block time, at most `MAX_BREAKPOINTS` schedules per call
+fn load_totals(storage: &mut dyn Storage, env: &Env) -> StdResult<VestingTotals> {
+    let mut totals = VESTING_TOTALS.load(storage)?;
+    for (nanos, (address, schedule_id)) in apply_breakpoints(storage, &mut totals, env.block.time, Some(MAX_BREAKPOINTS))? {
+        BREAKPOINTS.remove(storage, (nanos, (&address, schedule_id)));
     }
-    let (_, data): (Vec<Addr>, Vec<VestingData>) = account_vest_data.into_iter().unzip();
-    let total_vesting_info = compute_total_vesting_info(data)?;
+    Ok(totals)
+}
 
-    VESTING_TOTAL.save(deps.storage, &total_vesting_info, height)?;
+fn save_totals(storage: &mut dyn Storage, env: &Env, totals: &VestingTotals) -> StdResult<()> {
+    VESTING_TOTALS.save(storage, totals, env.block.height - 1)
+}
 
+// schedules enter the totals with their curves at the totals' time, which may lag the block time.
+// Breakpoints after it, the registration included, are applied when the totals catch up.
+fn add_account(storage: &mut dyn Storage, totals: &mut VestingTotals, account: &Account) -> StdResult<()> {
+    let (prevested, vested) = account.curves(totals.time)?;
+    totals.prevested.add(&prevested);
+    totals.vested.add(&vested);
+    totals.prevesting_amount += account.prevesting_amount;
+    totals.claimed_amount += account.claimed_amount;
+
+    for breakpoint in account.breakpoints()? {
+        if breakpoint > totals.time {
+            BREAKPOINTS.save(storage, (breakpoint.nanos(), (&account.address, account.schedule_id)), &true)?;
+        }
+    }
+    Ok(())
+}
 
-    Ok(Response::new().add_attribute("action", "snapshot").add_attribute("height", Uint64::new(_env.block.height)))
+fn remove_account(storage: &mut dyn Storage, totals: &mut VestingTotals, account: &Account) -> StdResult<()> {
+    let (prevested, vested) = account.curves(totals.time)?;
+    totals.prevested.sub(&prevested);
+    totals.vested.sub(&vested);
+    totals.prevesting_amount -= account.prevesting_amount;
+    totals.claimed_amount -= account.claimed_amount;
+
+    for breakpoint in account.breakpoints()? {
+        BREAKPOINTS.remove(storage, (breakpoint.nanos(), (&account.address, account.schedule_id)));
+    }
+    Ok(())
 }
 
 fn update_owner_address(deps: DepsMut, _env: Env, info: MessageInfo, owner_address: Addr) -> StdResult<Response>  {
@@ -102,75 +203,56 @@ fn update_owner_address(deps: DepsMut, _env: Env, info: MessageInfo, owner_addre
     Ok(Response::new().add_attribute("action", "update_owner_address").add_attribute("owner_address", &owner_address))
 }
 
-fn compute_total_vesting_info(account_vesting_data: Vec<VestingData>) -> StdResult<TotalVestingInfo> {
-    let total_prevesting= Uint128::zero();
-    let mut total_prevested = Uint128::zero();
-    let mut total_vested = Uint128::zero();
-    let mut total_claimed= Uint128::zero();
-    let mut total_vesting= Uint128::zero();
-    for account_data in account_vesting_data.into_iter() {
-        total_prevested += account_data.prevested_amount;
-        total_vested += account_data.vested_amount;
-        total_claimed += account_data.claimed_amount;
-
-        if account_data.vesting_amount > account_data.prevested_amount {
-            total_vesting += account_data.prevested_amount;
-        } else {
-            total_vesting += account_data.vesting_amount;
-        }
-    }
-
-    Ok(
-        TotalVestingInfo {
-            prevesting_amount: total_prevesting,
-            prevested_amount: total_prevested,
-            vesting_amount: total_vesting,
-            vested_amount: total_vested,
-            claimed_amount: total_claimed,
-        }
-    )
-}
-
-fn register_vesting_account(deps: DepsMut, env: Env, info: MessageInfo, address: Addr, start_time: Timestamp, end_time: Timestamp, vesting_amount: Uint128, prevesting_amount: Uint128) -> StdResult<Response> {
+fn register_vesting_account(deps: DepsMut, env: Env, info: MessageInfo, address: Addr, start_time: Timestamp, end_time: Timestamp, cliff_time: Option<Timestamp>, vesting_amount: Uint128, prevesting_amount: Uint128) -> StdResult<Response> {
     only_owner(deps.storage, info.sender.clone())?;
-    // vesting_account existence check
-    if ACCOUNTS.has(deps.storage, &address) {
-        return Err(StdError::generic_err("already exists"));
-    }
+    let schedule_id = NEXT_SCHEDULE_ID.may_load(deps.storage, &address)?.unwrap_or_default();
+    NEXT_SCHEDULE_ID.save(deps.storage, &address, &(schedule_id + 1))?;
 
     let account = Account {
         address: address.clone(),
+        schedule_id,
         prevesting_amount: prevesting_amount.clone(),
         vesting_amount: vesting_amount.clone(),
         registration_time: env.block.time,
         start_time: start_time,
         end_time: end_time,
+        cliff_time,
         claimed_amount: Uint128::zero(),
     };
     account.validate(&env.block)?;
 
+    // vesting amount must be covered by received tokens
+    let unallocated = UNALLOCATED.may_load(deps.storage)?.unwrap_or_default();
+    if vesting_amount > unallocated {
+        return Err(StdError::generic_err("insufficient unallocated funds"));
+    }
+    UNALLOCATED.save(deps.storage, &(unallocated - vesting_amount))?;
+
+    let mut totals = load_totals(deps.storage, &env)?;
     ACCOUNTS.save(
         deps.storage,
-        &address,
+        (&address, schedule_id),
         &account,
+        env.block.height - 1,
     )?;
-
-    let _ = snapshot(deps, env, info)?;
+    add_account(deps.storage, &mut totals, &account)?;
+    save_totals(deps.storage, &env, &totals)?;
 
     Ok(Response::new()
         .add_attribute("action", "register_vesting_account")
         .add_attribute("address", &address)
+        .add_attribute("schedule_id", schedule_id.to_string())
         .add_attribute("vesting_amount", vesting_amount.clone())
     )
 }
 
-fn deregister_vesting_account(deps: DepsMut, env: Env, info: MessageInfo, address: Addr, vested_token_recipient: Option<Addr>, left_vesting_token_recipient: Option<Addr>) -> StdResult<Response> {
+fn deregister_vesting_account(deps: DepsMut, env: Env, info: MessageInfo, address: Addr, schedule_id: u64, vested_token_recipient: Option<Addr>, left_vesting_token_recipient: Option<Addr>) -> StdResult<Response> {
     only_owner(deps.storage, info.sender.clone())?;
     let token_address = TOKEN_ADDRESS.load(deps.storage)?;
     let mut messages: Vec<WasmMsg> = vec![];
 
     // vesting_account existence check
-    let account = ACCOUNTS.may_load(deps.storage, &address)?;
+    let account = ACCOUNTS.may_load(deps.storage, (&address, schedule_id))?;
     if account.is_none() {
         return Err(StdError::generic_err("vesting entry is not found"));
     }
@@ -178,10 +260,10 @@ fn deregister_vesting_account(deps: DepsMut, env: Env, info: MessageInfo, addres
     let account = account.unwrap();
 
     // remove vesting account
-    ACCOUNTS.remove(deps.storage, &address);
-
-    let _ = snapshot(deps, env.clone(), info.clone())?; // save current snapshot
-    //VESTING_DATA.remove(deps.storage, &address, env.block.height)?;
+    let mut totals = load_totals(deps.storage, &env)?;
+    remove_account(deps.storage, &mut totals, &account)?;
+    save_totals(deps.storage, &env, &totals)?;
+    ACCOUNTS.remove(deps.storage, (&address, schedule_id), env.block.height - 1)?;
 
     let vested_amount = account
         .vested_amount(&env.block)?;
@@ -201,20 +283,27 @@ fn deregister_vesting_account(deps: DepsMut, env: Env, info: MessageInfo, addres
         messages.push(claimable_message);
     }
 
-    // transfer left vesting amount to owner or
-    // the given `left_vesting_token_recipient` address
+    // transfer left vesting amount to the given `left_vesting_token_recipient` address,
+    // or keep it unallocated for later registrations
     let left_vesting_amount = account.vesting_amount.checked_sub(vested_amount)?;
     if !left_vesting_amount.is_zero() {
-        let _recipient = left_vesting_token_recipient.unwrap_or(info.sender.clone());
-        let left_vesting_message = WasmMsg::Execute {
-            contract_addr: token_address.to_string(),
-            funds: vec![],
-            msg: to_binary(&Cw20ExecuteMsg::Transfer {
-                recipient: _recipient.to_string(),
-                amount: left_vesting_amount,
-            })?,
-        };
-        messages.push(left_vesting_message);
+        match left_vesting_token_recipient {
+            Some(_recipient) => {
+                let left_vesting_message = WasmMsg::Execute {
+                    contract_addr: token_address.to_string(),
+                    funds: vec![],
+                    msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                        recipient: _recipient.to_string(),
+                        amount: left_vesting_amount,
+                    })?,
+                };
+                messages.push(left_vesting_message);
+            },
+            None => {
+                let unallocated = UNALLOCATED.may_load(deps.storage)?.unwrap_or_default();
+                UNALLOCATED.save(deps.storage, &(unallocated + left_vesting_amount))?;
+            },
+        }
     }
 
     Ok(
@@ -222,6 +311,7 @@ fn deregister_vesting_account(deps: DepsMut, env: Env, info: MessageInfo, addres
             .add_messages(messages)
             .add_attribute("action", "deregister_vesting_account")
             .add_attribute("address", address)
+            .add_attribute("schedule_id", schedule_id.to_string())
             .add_attribute("vesting_amount", account.vesting_amount)
             .add_attribute("claimable_amount", claimable_amount)
             .add_attribute("vested_amount", vested_amount)
@@ -229,6 +319,32 @@ fn deregister_vesting_account(deps: DepsMut, env: Env, info: MessageInfo, addres
     )
 }
 
+fn withdraw_unallocated(deps: DepsMut, _env: Env, info: MessageInfo, amount: Uint128, recipient: Option<Addr>) -> StdResult<Response> {
+    only_owner(deps.storage, info.sender.clone())?;
+
+    let unallocated = UNALLOCATED.may_load(deps.storage)?.unwrap_or_default();
+    if amount > unallocated {
+        return Err(StdError::generic_err("insufficient unallocated funds"));
+    }
+    UNALLOCATED.save(deps.storage, &(unallocated - amount))?;
+
+    let _recipient = recipient.unwrap_or(info.sender);
+    let token_address = TOKEN_ADDRESS.load(deps.storage)?;
+    Ok(Response::new()
+        .add_message(WasmMsg::Execute {
+            contract_addr: token_address.to_string(),
+            funds: vec![],
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: _recipient.to_string(),
+                amount,
+            })?,
+        })
+        .add_attribute("action", "withdraw_unallocated")
+        .add_attribute("recipient", _recipient)
+        .add_attribute("amount", amount)
+    )
+}
+
 fn claim(deps: DepsMut, env: Env, info: MessageInfo, recipient: Option<Addr>) -> StdResult<Response> {
     let _recipient = match recipient {
         None => info.clone().sender,
@@ -236,26 +352,33 @@ fn claim(deps: DepsMut, env: Env, info: MessageInfo, recipient: Option<Addr>) ->
     };
     let token_address = TOKEN_ADDRESS.load(deps.storage)?;
 
-    let account = ACCOUNTS.may_load(deps.storage, &_recipient)?;
-    if account.is_none() {
+    let accounts: Vec<Account> = ACCOUNTS
+        .prefix(&_recipient)
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(_, account)| account))
+        .collect::<StdResult<_>>()?;
+    if accounts.is_empty() {
         return Err(StdError::generic_err("vesting entry is not found"));
     }
 
-    let mut account = account.unwrap();
-    let vested_amount = account.vested_amount(&env.block)?;
-    let claimed_amount = account.claimed_amount;
+    // claims the vested amount of every schedule
+    let mut claimable_amount = Uint128::zero();
+    for mut account in accounts.into_iter() {
+        let vested_amount = account.vested_amount(&env.block)?;
+        let claimed_amount = account.claimed_amount;
+        if vested_amount == claimed_amount {
+            continue;
+        }
 
-    let claimable_amount = vested_amount.checked_sub(claimed_amount)?;
+        claimable_amount += vested_amount.checked_sub(claimed_amount)?;
 
-    account.claimed_amount = vested_amount;
-    ACCOUNTS.save(deps.storage, &_recipient, &account)?;
-    // if account.claimed_amount == account.vesting_amount {
-    //     ACCOUNTS.remove(deps.storage, &_recipient);
-    //     VESTING_DATA.remove(deps.storage, &addr, &account_data, height)?;
-    // } else {
-    //     ACCOUNTS.save(deps.storage, &_recipient, &account)?;
-    // }
-    let _ = snapshot(deps, env.clone(), info.clone())?;
+        account.claimed_amount = vested_amount;
+        ACCOUNTS.save(deps.storage, (&_recipient, account.schedule_id), &account, env.block.height - 1)?;
+    }
+
+    let mut totals = load_totals(deps.storage, &env)?;
+    totals.claimed_amount += claimable_amount;
+    save_totals(deps.storage, &env, &totals)?;
 
     let res = Response::new()
         .add_message(WasmMsg::Execute {
@@ -283,11 +406,20 @@ pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
             height,
         } => to_binary(&query_vesting_account(deps, env, address, height)?),
         QueryMsg::TokenAddress {} => to_binary(&query_token_address(deps, env)?),
+        QueryMsg::VestingSchedules { address } => to_binary(&query_vesting_schedules(deps, env, address)?),
         QueryMsg::VestingTotal { height } => to_binary(&query_vesting_total(deps, env, height)?),
         QueryMsg::Info {} => to_binary(&query_info(deps)?),
+        QueryMsg::Unallocated {} => to_binary(&query_unallocated(deps)?),
     }
 }
 
+fn query_unallocated(deps: Deps) -> StdResult<UnallocatedResponse> {
+    let unallocated_amount = UNALLOCATED.may_load(deps.storage)?.unwrap_or_default();
+    Ok(UnallocatedResponse {
+        unallocated_amount,
+    })
+}
+
 fn query_owner_address(deps: Deps, _env: Env) -> StdResult<OwnerAddressResponse> {
     let owner_address = OWNER_ADDRESS.load(deps.storage)?;
     Ok(OwnerAddressResponse {
@@ -302,9 +434,48 @@ fn query_token_address(deps: Deps, _env: Env) -> StdResult<TokenAddressResponse>
     })
 }
 
+// totals and block at the given height. The current height is evaluated at the block time with
+// the breakpoints passed since the totals were last written, past heights at the time recorded
+// by then.
+fn totals_at_height(deps: Deps, env: &Env, height: Option<u64>) -> StdResult<Option<(VestingTotals, BlockInfo)>> {
+    let height = height.unwrap_or(env.block.height);
+    let totals = if height >= env.block.height {
+        match VESTING_TOTALS.may_load(deps.storage)? {
+            Some(mut totals) => {
+                apply_breakpoints(deps.storage, &mut totals, env.block.time, None)?;
+                Some(totals)
+            },
+            None => None,
+        }
+    } else {
+        VESTING_TOTALS.may_load_at_height(deps.storage, height)?
+    };
+    Ok(totals.map(|totals| {
+        let block = BlockInfo {
+            height,
+            time: totals.time,
+            chain_id: env.block.chain_id.clone(),
+        };
+        (totals, block)
+    }))
+}
+
+// heights up to the migration to computed totals are answered from the snapshots taken before it
+fn is_legacy_height(storage: &dyn Storage, height: u64) -> StdResult<bool> {
+    Ok(matches!(MIGRATION_HEIGHT.may_load(storage)?, Some(migration_height) if height <= migration_height))
+}
+
 fn query_vesting_total(deps: Deps, env: Env, height: Option<u64>) -> StdResult<VestingTotalResponse> {
     let input_height = height.unwrap_or(env.block.height);
-    let total_vesting_info = VESTING_TOTAL.may_load_at_height(deps.storage, input_height)?.unwrap_or_default();
+    if is_legacy_height(deps.storage, input_height)? {
+        let info = LEGACY_VESTING_TOTAL.may_load_at_height(deps.storage, input_height)?.unwrap_or_default();
+        return Ok(VestingTotalResponse { info });
+    }
+
+    let total_vesting_info = match totals_at_height(deps, &env, height)? {
+        Some((totals, block)) => totals.info(block.time)?,
+        None => TotalVestingInfo::default(),
+    };
 
     Ok(VestingTotalResponse {
         info: total_vesting_info,
@@ -313,9 +484,66 @@ fn query_vesting_total(deps: Deps, env: Env, height: Option<u64>) -> StdResult<V
 
 fn query_vesting_account(deps: Deps, env: Env, address: Addr, height: Option<u64>) -> StdResult<VestingAccountResponse> {
     let input_height = height.unwrap_or(env.block.height);
-    let vesting_data = VESTING_DATA.may_load_at_height(deps.storage, &address, input_height)?.unwrap_or_default();
+    if is_legacy_height(deps.storage, input_height)? {
+        let vesting_data = LEGACY_VESTING_DATA.may_load_at_height(deps.storage, &address, input_height)?.unwrap_or_default();
+        return Ok(VestingAccountResponse { address, vestings: vesting_data });
+    }
 
-    Ok(VestingAccountResponse { address, vestings: vesting_data })
+    let block = match totals_at_height(deps, &env, height)? {
+        Some((_, block)) => block,
+        None => return Ok(VestingAccountResponse { address, vestings: VestingData::default() }),
+    };
+    // schedules removed since `height` are gone from the current keys, walk all ids ever given
+    let next_schedule_id = NEXT_SCHEDULE_ID.may_load(deps.storage, &address)?.unwrap_or_default();
+    let mut vesting_data: Option<VestingData> = None;
+    for schedule_id in 0..next_schedule_id {
+        let account = match ACCOUNTS.may_load_at_height(deps.storage, (&address, schedule_id), block.height)? {
+            Some(account) => account,
+            None => continue,
+        };
+        let data = get_vesting_data_from_account(account, &block)?;
+        vesting_data = Some(match vesting_data {
+            Some(sum) => add_vesting_data(sum, data),
+            None => data,
+        });
+    }
+
+    Ok(VestingAccountResponse { address, vestings: vesting_data.unwrap_or_default() })
+}
+
+// sums the amounts of two schedules, spanning the earliest registration, start and cliff to the latest end
+fn add_vesting_data(a: VestingData, b: VestingData) -> VestingData {
+    VestingData {
+        prevesting_amount: a.prevesting_amount + b.prevesting_amount,
+        prevested_amount: a.prevested_amount + b.prevested_amount,
+        vesting_amount: a.vesting_amount + b.vesting_amount,
+        vested_amount: a.vested_amount + b.vested_amount,
+        claimable_amount: a.claimable_amount + b.claimable_amount,
+        claimed_amount: a.claimed_amount + b.claimed_amount,
+        registration_time: a.registration_time.min(b.registration_time),
+        start_time: a.start_time.min(b.start_time),
+        end_time: a.end_time.max(b.end_time),
+        cliff_time: match (a.cliff_time, b.cliff_time) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        },
+    }
+}
+
+fn query_vesting_schedules(deps: Deps, env: Env, address: Addr) -> StdResult<VestingSchedulesResponse> {
+    let schedules = ACCOUNTS
+        .prefix(&address)
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| {
+            let (schedule_id, account) = item?;
+            Ok(VestingSchedule {
+                schedule_id,
+                vestings: get_vesting_data_from_account(account, &env.block)?,
+            })
+        })
+        .collect::<StdResult<_>>()?;
+
+    Ok(VestingSchedulesResponse { address, schedules })
 }
 
 pub fn query_info(deps: Deps) -> StdResult<InfoResponse> {
@@ -324,20 +552,73 @@ pub fn query_info(deps: Deps) -> StdResult<InfoResponse> {
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
+pub fn migrate(deps: DepsMut, env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
     // Set contract to version to latest
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    // accounts become the first schedule of their address
+    let legacy_accounts: Vec<(Addr, Account)> = LEGACY_ACCOUNTS
+        .range(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<_>>()?;
+    for (address, account) in legacy_accounts.iter() {
+        LEGACY_ACCOUNTS.remove(deps.storage, address);
+        ACCOUNTS.save(deps.storage, (address, 0), account, env.block.height)?;
+        NEXT_SCHEDULE_ID.save(deps.storage, address, &1)?;
+    }
+
+    // contracts funded by plain transfers get the balance not owed to accounts as unallocated
+    if UNALLOCATED.may_load(deps.storage)?.is_none() {
+        let token_address = TOKEN_ADDRESS.load(deps.storage)?;
+        let balance: BalanceResponse = deps.querier.query_wasm_smart(
+            token_address,
+            &Cw20QueryMsg::Balance { address: env.contract.address.to_string() },
+        )?;
+        let owed = ACCOUNTS
+            .range(deps.storage, None, None, Order::Ascending)
+            .map(|item| item.map(|(_, account)| account.vesting_amount - account.claimed_amount))
+            .sum::<StdResult<Uint128>>()?;
+        UNALLOCATED.save(deps.storage, &balance.balance.saturating_sub(owed))?;
+    }
+
+    // totals replaced snapshots of every account, build them once from the accounts and keep
+    // answering heights up to the migration from the snapshots
+    if VESTING_TOTALS.may_load(deps.storage)?.is_none() {
+        let accounts: Vec<Account> = ACCOUNTS
+            .range(deps.storage, None, None, Order::Ascending)
+            .map(|item| item.map(|(_, account)| account))
+            .collect::<StdResult<_>>()?;
+        let mut totals = VestingTotals {
+            time: env.block.time,
+            ..VestingTotals::default()
+        };
+        for account in accounts.iter() {
+            add_account(deps.storage, &mut totals, account)?;
+        }
+        VESTING_TOTALS.save(deps.storage, &totals, env.block.height)?;
+        MIGRATION_HEIGHT.save(deps.storage, &env.block.height)?;
+    }
+
     Ok(Response::default())
 }
 
 #[cfg(test)]
 mod testing {
-    use cosmwasm_std::{Addr, from_binary, testing::{mock_dependencies, mock_env, mock_info}};
+    use cosmwasm_std::{Addr, ContractResult, from_binary, SubMsg, SystemResult, testing::{mock_dependencies, mock_env, mock_info}, WasmQuery};
 
     use crate::msg::InstantiateMsg;
 
     use super::*;
 
+    fn fund(deps: DepsMut, env: Env, amount: u128) {
+        let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "addr0001".to_string(),
+            amount: Uint128::new(amount),
+            msg: to_binary(&ReceiveMsg::Fund {}).unwrap(),
+        });
+        let info = mock_info("token0001", &[]);
+        execute(deps, env, info, msg).unwrap();
+    }
+
     #[test]
     fn proper_instantiation() {
         let mut deps = mock_dependencies();
@@ -385,8 +666,10 @@ mod testing {
             prevesting_amount: Uint128::from(10u32),
             start_time: Timestamp::from_nanos(100),
             end_time: Timestamp::from_nanos(200),
+            cliff_time: None,
         };
         let info = mock_info("addr0001", &[]);
+        fund(deps.as_mut(), env.clone(), 100);
         let _ = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
 
         env.block.height += 20;
@@ -400,7 +683,7 @@ mod testing {
             address: Addr::unchecked("addr0002".to_string()),
             vestings: VestingData {
                 prevesting_amount: Uint128::from(10u32),
-                prevested_amount: Uint128::from(10u32),
+                prevested_amount: Uint128::from(50u32),
                 vesting_amount: Uint128::from(100u32),
                 vested_amount: Uint128::from(0u32),
                 claimable_amount: Uint128::from(0u32),
@@ -408,6 +691,7 @@ mod testing {
                 registration_time: Timestamp::from_nanos(0),
                 start_time: Timestamp::from_nanos(100),
                 end_time: Timestamp::from_nanos(200),
+                cliff_time: None,
             },
         });
 
@@ -428,12 +712,13 @@ mod testing {
                 prevesting_amount: Uint128::from(10u32),
                 prevested_amount: Uint128::from(100u32),
                 vesting_amount: Uint128::from(100u32),
-                vested_amount: Uint128::from(5u32),
-                claimable_amount: Uint128::from(5u32),
+                vested_amount: Uint128::from(10u32),
+                claimable_amount: Uint128::from(10u32),
                 claimed_amount: Uint128::zero(),
                 registration_time: Timestamp::from_nanos(0),
                 start_time: Timestamp::from_nanos(100),
                 end_time: Timestamp::from_nanos(200),
+                cliff_time: None,
             },
         })
     }
@@ -462,8 +747,10 @@ mod testing {
             prevesting_amount: Uint128::from(10u32),
             start_time: Timestamp::from_nanos(100),
             end_time: Timestamp::from_nanos(200),
+            cliff_time: None,
         };
         let info = mock_info("addr0001", &[]);
+        fund(deps.as_mut(), env.clone(), 100);
         let _ = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
 
         // ##### TIME 1 ##### (10 seconds before start_time)
@@ -489,6 +776,7 @@ mod testing {
                 registration_time: Timestamp::from_nanos(0),
                 start_time: Timestamp::from_nanos(100),
                 end_time: Timestamp::from_nanos(200),
+                cliff_time: None,
             },
         });
 
@@ -518,6 +806,7 @@ mod testing {
                 registration_time: Timestamp::from_nanos(0),
                 start_time: Timestamp::from_nanos(100),
                 end_time: Timestamp::from_nanos(200),
+                cliff_time: None,
             },
         });
 
@@ -530,19 +819,20 @@ mod testing {
         let vesting_response: VestingAccountResponse = from_binary(&res).unwrap();
 
 
-        // it should be freezed since there were no snapshot in the middle
+        // the current height is computed at the block time without a snapshot in the middle
         assert_eq!(vesting_response, VestingAccountResponse {
             address: Addr::unchecked("addr0002".to_string()),
             vestings: VestingData {
                 prevesting_amount: Uint128::from(10u32),
                 prevested_amount: Uint128::from(100u32),
                 vesting_amount: Uint128::from(100u32),
-                vested_amount: Uint128::from(10u32),
-                claimable_amount: Uint128::from(0u32),
+                vested_amount: Uint128::from(15u32),
+                claimable_amount: Uint128::from(5u32),
                 claimed_amount: Uint128::from(10u32),
                 registration_time: Timestamp::from_nanos(0),
                 start_time: Timestamp::from_nanos(100),
                 end_time: Timestamp::from_nanos(200),
+                cliff_time: None,
             },
         });
 
@@ -566,6 +856,7 @@ mod testing {
                     registration_time: Timestamp::from_nanos(0),
                     start_time: Timestamp::from_nanos(100),
                     end_time: Timestamp::from_nanos(200),
+                    cliff_time: None,
                 },
             }
         );
@@ -595,8 +886,10 @@ mod testing {
             prevesting_amount: Uint128::from(10u32),
             start_time: Timestamp::from_nanos(100),
             end_time: Timestamp::from_nanos(200),
+            cliff_time: None,
         };
         let info = mock_info("addr0001", &[]);
+        fund(deps.as_mut(), env.clone(), 100);
         let _ = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
 
         let res = query(deps.as_ref(), env.clone(), QueryMsg::VestingAccount {
@@ -616,6 +909,7 @@ mod testing {
                 registration_time: Timestamp::from_nanos(0),
                 start_time: Timestamp::from_nanos(100),
                 end_time: Timestamp::from_nanos(200),
+                cliff_time: None,
             },
         });
 
@@ -644,6 +938,7 @@ mod testing {
                 registration_time: Timestamp::from_nanos(0),
                 start_time: Timestamp::from_nanos(100),
                 end_time: Timestamp::from_nanos(200),
+                cliff_time: None,
             },
         });
 
@@ -673,6 +968,7 @@ mod testing {
                 registration_time: Timestamp::from_nanos(0),
                 start_time: Timestamp::from_nanos(100),
                 end_time: Timestamp::from_nanos(200),
+                cliff_time: None,
             },
         });
 
@@ -695,17 +991,606 @@ mod testing {
                 registration_time: Timestamp::from_nanos(0),
                 start_time: Timestamp::from_nanos(100),
                 end_time: Timestamp::from_nanos(200),
+                cliff_time: None,
             },
         });
     }
 
+    #[test]
+    fn vesting_total() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg {
+            token_address: Addr::unchecked("token0001".to_string()),
+            owner_address: Some(Addr::unchecked("addr0001".to_string())),
+        };
+
+        let mut env = mock_env();
+        env.block.time = Timestamp::from_nanos(0);
+        env.block.height = 1000;
+        let info = mock_info("addr0000", &[]);
+        let _res = instantiate(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+        // two accounts with different schedules
+        let info = mock_info("addr0001", &[]);
+        fund(deps.as_mut(), env.clone(), 300);
+        let msg = ExecuteMsg::RegisterVestingAccount {
+            address: Addr::unchecked("addr0002".to_string()),
+            vesting_amount: Uint128::from(100u32),
+            prevesting_amount: Uint128::from(10u32),
+            start_time: Timestamp::from_nanos(100),
+            end_time: Timestamp::from_nanos(200),
+            cliff_time: None,
+        };
+        let _ = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        let msg = ExecuteMsg::RegisterVestingAccount {
+            address: Addr::unchecked("addr0003".to_string()),
+            vesting_amount: Uint128::from(200u32),
+            prevesting_amount: Uint128::zero(),
+            start_time: Timestamp::from_nanos(50),
+            end_time: Timestamp::from_nanos(450),
+            cliff_time: None,
+        };
+        let _ = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+        let total = |deps: Deps, env: Env, height: Option<u64>| -> TotalVestingInfo {
+            let res = query(deps, env, QueryMsg::VestingTotal { height }).unwrap();
+            from_binary::<VestingTotalResponse>(&res).unwrap().info
+        };
+
+        // totals follow the schedules between snapshots
+        env.block.height += 1;
+        env.block.time = Timestamp::from_nanos(40);
+        let _ = execute(deps.as_mut(), env.clone(), info.clone(), ExecuteMsg::Snapshot {}).unwrap();
+        assert_eq!(total(deps.as_ref(), env.clone(), None), TotalVestingInfo {
+            prevesting_amount: Uint128::from(10u32),
+            prevested_amount: Uint128::from(200u32),
+            vesting_amount: Uint128::from(200u32),
+            vested_amount: Uint128::zero(),
+            claimed_amount: Uint128::zero(),
+        });
+
+        env.block.height += 1;
+        env.block.time = Timestamp::from_nanos(150);
+        let _ = execute(deps.as_mut(), env.clone(), info.clone(), ExecuteMsg::Snapshot {}).unwrap();
+        assert_eq!(total(deps.as_ref(), env.clone(), None), TotalVestingInfo {
+            prevesting_amount: Uint128::from(10u32),
+            prevested_amount: Uint128::from(300u32),
+            vesting_amount: Uint128::from(300u32),
+            vested_amount: Uint128::from(100u32),
+            claimed_amount: Uint128::zero(),
+        });
+
+        // claim at 150, then deregister the other account at 250
+        let info = mock_info("addr0002", &[]);
+        let msg = ExecuteMsg::Claim { recipient: None };
+        let _ = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+        env.block.height += 1;
+        env.block.time = Timestamp::from_nanos(250);
+        let info = mock_info("addr0001", &[]);
+        let msg = ExecuteMsg::DeregisterVestingAccount {
+            address: Addr::unchecked("addr0003".to_string()),
+            schedule_id: 0,
+            vested_token_recipient: None,
+            left_vesting_token_recipient: None,
+        };
+        let _ = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+        assert_eq!(total(deps.as_ref(), env.clone(), None), TotalVestingInfo {
+            prevesting_amount: Uint128::from(10u32),
+            prevested_amount: Uint128::from(100u32),
+            vesting_amount: Uint128::from(100u32),
+            vested_amount: Uint128::from(100u32),
+            claimed_amount: Uint128::from(50u32),
+        });
+
+        // past heights are computed at the time recorded for them
+        assert_eq!(total(deps.as_ref(), env.clone(), Some(1002)), TotalVestingInfo {
+            prevesting_amount: Uint128::from(10u32),
+            prevested_amount: Uint128::from(300u32),
+            vesting_amount: Uint128::from(300u32),
+            vested_amount: Uint128::from(100u32),
+            claimed_amount: Uint128::from(50u32),
+        });
+        let res = query(deps.as_ref(), env, QueryMsg::VestingAccount {
+            address: Addr::unchecked("addr0003".to_string()), height: Some(1002),
+        }).unwrap();
+        let vesting_response: VestingAccountResponse = from_binary(&res).unwrap();
+        assert_eq!(vesting_response.vestings.vested_amount, Uint128::from(50u32));
+    }
+
+    #[test]
+    fn multiple_schedules() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg {
+            token_address: Addr::unchecked("token0001".to_string()),
+            owner_address: Some(Addr::unchecked("addr0001".to_string())),
+        };
+
+        let mut env = mock_env();
+        env.block.time = Timestamp::from_nanos(0);
+        let info = mock_info("addr0000", &[]);
+        let _res = instantiate(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+        // a second grant to the same address
+        let info = mock_info("addr0001", &[]);
+        fund(deps.as_mut(), env.clone(), 300);
+        let msg = ExecuteMsg::RegisterVestingAccount {
+            address: Addr::unchecked("addr0002".to_string()),
+            vesting_amount: Uint128::from(100u32),
+            prevesting_amount: Uint128::zero(),
+            start_time: Timestamp::from_nanos(100),
+            end_time: Timestamp::from_nanos(200),
+            cliff_time: None,
+        };
+        let res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        assert_eq!(res.attributes[2], attr("schedule_id", "0"));
+        let msg = ExecuteMsg::RegisterVestingAccount {
+            address: Addr::unchecked("addr0002".to_string()),
+            vesting_amount: Uint128::from(200u32),
+            prevesting_amount: Uint128::zero(),
+            start_time: Timestamp::from_nanos(150),
+            end_time: Timestamp::from_nanos(350),
+            cliff_time: None,
+        };
+        let res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        assert_eq!(res.attributes[2], attr("schedule_id", "1"));
+
+        env.block.height += 1;
+        env.block.time = Timestamp::from_nanos(175);
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::VestingSchedules {
+            address: Addr::unchecked("addr0002".to_string()),
+        }).unwrap();
+        let schedules: VestingSchedulesResponse = from_binary(&res).unwrap();
+        assert_eq!(schedules.schedules.len(), 2);
+        assert_eq!(schedules.schedules[0].schedule_id, 0);
+        assert_eq!(schedules.schedules[0].vestings.claimable_amount, Uint128::from(75u32));
+        assert_eq!(schedules.schedules[1].schedule_id, 1);
+        assert_eq!(schedules.schedules[1].vestings.claimable_amount, Uint128::from(25u32));
+
+        // the account sums up its schedules
+        let _ = execute(deps.as_mut(), env.clone(), info.clone(), ExecuteMsg::Snapshot {}).unwrap();
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::VestingAccount {
+            address: Addr::unchecked("addr0002".to_string()), height: None,
+        }).unwrap();
+        let vesting_response: VestingAccountResponse = from_binary(&res).unwrap();
+        assert_eq!(vesting_response.vestings, VestingData {
+            prevesting_amount: Uint128::zero(),
+            prevested_amount: Uint128::from(300u32),
+            vesting_amount: Uint128::from(300u32),
+            vested_amount: Uint128::from(100u32),
+            claimable_amount: Uint128::from(100u32),
+            claimed_amount: Uint128::zero(),
+            registration_time: Timestamp::from_nanos(0),
+            start_time: Timestamp::from_nanos(100),
+            end_time: Timestamp::from_nanos(350),
+            cliff_time: None,
+        });
+
+        // claim pays out every schedule
+        let msg = ExecuteMsg::Claim { recipient: None };
+        let res = execute(deps.as_mut(), env.clone(), mock_info("addr0002", &[]), msg).unwrap();
+        assert_eq!(res.attributes[2], attr("amount", "100"));
+
+        // deregistering the first schedule keeps the second
+        env.block.height += 1;
+        env.block.time = Timestamp::from_nanos(250);
+        let msg = ExecuteMsg::DeregisterVestingAccount {
+            address: Addr::unchecked("addr0002".to_string()),
+            schedule_id: 0,
+            vested_token_recipient: None,
+            left_vesting_token_recipient: None,
+        };
+        let _ = execute(deps.as_mut(), env.clone(), info.clone(), msg.clone()).unwrap();
+        let err = execute(deps.as_mut(), env.clone(), info, msg).unwrap_err();
+        assert_eq!(err, StdError::generic_err("vesting entry is not found"));
+
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::VestingSchedules {
+            address: Addr::unchecked("addr0002".to_string()),
+        }).unwrap();
+        let schedules: VestingSchedulesResponse = from_binary(&res).unwrap();
+        assert_eq!(schedules.schedules.len(), 1);
+        assert_eq!(schedules.schedules[0].schedule_id, 1);
+        assert_eq!(schedules.schedules[0].vestings.claimable_amount, Uint128::from(75u32));
+
+        // while the previous height still counts both
+        let res = query(deps.as_ref(), env, QueryMsg::VestingAccount {
+            address: Addr::unchecked("addr0002".to_string()), height: Some(12346),
+        }).unwrap();
+        let vesting_response: VestingAccountResponse = from_binary(&res).unwrap();
+        assert_eq!(vesting_response.vestings.vesting_amount, Uint128::from(300u32));
+        assert_eq!(vesting_response.vestings.claimed_amount, Uint128::from(100u32));
+    }
+
+    #[test]
+    fn cliff() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg {
+            token_address: Addr::unchecked("token0001".to_string()),
+            owner_address: Some(Addr::unchecked("addr0001".to_string())),
+        };
+
+        let mut env = mock_env();
+        env.block.time = Timestamp::from_nanos(0);
+        let info = mock_info("addr0000", &[]);
+        let _res = instantiate(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+        let info = mock_info("addr0001", &[]);
+        fund(deps.as_mut(), env.clone(), 100);
+        let msg = ExecuteMsg::RegisterVestingAccount {
+            address: Addr::unchecked("addr0002".to_string()),
+            vesting_amount: Uint128::from(100u32),
+            prevesting_amount: Uint128::zero(),
+            start_time: Timestamp::from_nanos(100),
+            end_time: Timestamp::from_nanos(200),
+            cliff_time: Some(Timestamp::from_nanos(150)),
+        };
+        let _ = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+        let account = |deps: Deps, env: Env| -> VestingData {
+            let res = query(deps, env, QueryMsg::VestingAccount {
+                address: Addr::unchecked("addr0002".to_string()), height: None,
+            }).unwrap();
+            from_binary::<VestingAccountResponse>(&res).unwrap().vestings
+        };
+        let vested_total = |deps: Deps, env: Env| -> Uint128 {
+            let res = query(deps, env, QueryMsg::VestingTotal { height: None }).unwrap();
+            from_binary::<VestingTotalResponse>(&res).unwrap().info.vested_amount
+        };
+
+        // nothing claimable before the cliff
+        env.block.height += 1;
+        env.block.time = Timestamp::from_nanos(149);
+        let _ = execute(deps.as_mut(), env.clone(), mock_info("addr0003", &[]), ExecuteMsg::Snapshot {}).unwrap();
+        let vestings = account(deps.as_ref(), env.clone());
+        assert_eq!(vestings.cliff_time, Some(Timestamp::from_nanos(150)));
+        assert_eq!(vestings.claimable_amount, Uint128::zero());
+        assert_eq!(vested_total(deps.as_ref(), env.clone()), Uint128::zero());
+
+        // the amount vested since the start unlocks at the cliff
+        env.block.height += 1;
+        env.block.time = Timestamp::from_nanos(150);
+        let _ = execute(deps.as_mut(), env.clone(), mock_info("addr0003", &[]), ExecuteMsg::Snapshot {}).unwrap();
+        assert_eq!(account(deps.as_ref(), env.clone()).claimable_amount, Uint128::from(50u32));
+        assert_eq!(vested_total(deps.as_ref(), env.clone()), Uint128::from(50u32));
+
+        let msg = ExecuteMsg::Claim { recipient: None };
+        let res = execute(deps.as_mut(), env.clone(), mock_info("addr0002", &[]), msg).unwrap();
+        assert_eq!(res.attributes[2], attr("amount", "50"));
+
+        env.block.height += 1;
+        env.block.time = Timestamp::from_nanos(175);
+        let _ = execute(deps.as_mut(), env.clone(), mock_info("addr0003", &[]), ExecuteMsg::Snapshot {}).unwrap();
+        assert_eq!(account(deps.as_ref(), env.clone()).claimable_amount, Uint128::from(25u32));
+        assert_eq!(vested_total(deps.as_ref(), env), Uint128::from(75u32));
+    }
+
+    #[test]
+    fn snapshot_breakpoint_limit() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg {
+            token_address: Addr::unchecked("token0001".to_string()),
+            owner_address: Some(Addr::unchecked("addr0001".to_string())),
+        };
+
+        let mut env = mock_env();
+        env.block.time = Timestamp::from_nanos(0);
+        let info = mock_info("addr0000", &[]);
+        let _res = instantiate(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+        // schedules with a breakpoint at their start and one at their end
+        let info = mock_info("addr0001", &[]);
+        let register = |deps: DepsMut, env: Env, i: u64| -> StdResult<Response> {
+            let msg = ExecuteMsg::RegisterVestingAccount {
+                address: Addr::unchecked(format!("addr{:04}", 100 + i)),
+                vesting_amount: Uint128::from(10u32),
+                prevesting_amount: Uint128::zero(),
+                start_time: env.block.time.plus_nanos(100 + i),
+                end_time: env.block.time.plus_nanos(1000 + i),
+                cliff_time: None,
+            };
+            execute(deps, env, mock_info("addr0001", &[]), msg)
+        };
+        fund(deps.as_mut(), env.clone(), 3010);
+        for i in 0..150 {
+            let _ = register(deps.as_mut(), env.clone(), i).unwrap();
+        }
+
+        let total = |deps: Deps, env: Env| -> TotalVestingInfo {
+            let res = query(deps, env, QueryMsg::VestingTotal { height: None }).unwrap();
+            from_binary::<VestingTotalResponse>(&res).unwrap().info
+        };
+
+        // a snapshot moves 100 breakpoints and stops right before the next one
+        env.block.height += 1;
+        env.block.time = Timestamp::from_nanos(2000);
+        let res = execute(deps.as_mut(), env.clone(), info.clone(), ExecuteMsg::Snapshot {}).unwrap();
+        assert_eq!(res.attributes[2], attr("time", "0.000000199"));
+        let full = TotalVestingInfo {
+            prevesting_amount: Uint128::zero(),
+            prevested_amount: Uint128::from(1500u32),
+            vesting_amount: Uint128::from(1500u32),
+            vested_amount: Uint128::from(1500u32),
+            claimed_amount: Uint128::zero(),
+        };
+        // queries apply the breakpoints left at the block time
+        assert_eq!(total(deps.as_ref(), env.clone()), full);
+
+        // anyone can move the rest
+        let res = execute(deps.as_mut(), env.clone(), mock_info("addr0003", &[]), ExecuteMsg::Snapshot {}).unwrap();
+        assert_eq!(res.attributes[2], attr("time", "0.000001049"));
+        let res = execute(deps.as_mut(), env.clone(), mock_info("addr0003", &[]), ExecuteMsg::Snapshot {}).unwrap();
+        assert_eq!(res.attributes[2], attr("time", "0.000002000"));
+        assert_eq!(total(deps.as_ref(), env.clone()), full);
+
+        // registering and deregistering go on while the totals catch up
+        for i in 0..150 {
+            let _ = register(deps.as_mut(), env.clone(), i).unwrap();
+        }
+        env.block.height += 1;
+        env.block.time = Timestamp::from_nanos(5000);
+        let _ = register(deps.as_mut(), env.clone(), 150).unwrap();
+        let msg = ExecuteMsg::DeregisterVestingAccount {
+            address: Addr::unchecked("addr0100".to_string()),
+            schedule_id: 1,
+            vested_token_recipient: None,
+            left_vesting_token_recipient: None,
+        };
+        let _ = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        let caught_up = TotalVestingInfo {
+            prevesting_amount: Uint128::zero(),
+            prevested_amount: Uint128::from(2990u32),
+            vesting_amount: Uint128::from(2990u32),
+            vested_amount: Uint128::from(2990u32),
+            claimed_amount: Uint128::zero(),
+        };
+        assert_eq!(total(deps.as_ref(), env.clone()), caught_up);
+
+        let res = execute(deps.as_mut(), env.clone(), info.clone(), ExecuteMsg::Snapshot {}).unwrap();
+        assert_ne!(res.attributes[2], attr("time", "0.000005000"));
+        let res = execute(deps.as_mut(), env.clone(), info, ExecuteMsg::Snapshot {}).unwrap();
+        assert_eq!(res.attributes[2], attr("time", "0.000005000"));
+        assert_eq!(total(deps.as_ref(), env.clone()), caught_up);
+
+        // the schedule registered while the totals lagged vests with them
+        env.block.height += 1;
+        env.block.time = Timestamp::from_nanos(5250 + 900);
+        assert_eq!(total(deps.as_ref(), env).vested_amount, Uint128::from(3000u32));
+    }
+
+    #[test]
+    fn register_requires_funds() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg {
+            token_address: Addr::unchecked("token0001".to_string()),
+            owner_address: Some(Addr::unchecked("addr0001".to_string())),
+        };
+
+        let mut env = mock_env();
+        env.block.time = Timestamp::from_nanos(0);
+        let info = mock_info("addr0000", &[]);
+        let _res = instantiate(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+        let register = ReceiveMsg::RegisterVestingAccount {
+            address: Addr::unchecked("addr0002".to_string()),
+            vesting_amount: Uint128::from(100u32),
+            prevesting_amount: Uint128::zero(),
+            start_time: Timestamp::from_nanos(100),
+            end_time: Timestamp::from_nanos(200),
+            cliff_time: None,
+        };
+
+        // nothing received yet
+        let msg = ExecuteMsg::RegisterVestingAccount {
+            address: Addr::unchecked("addr0002".to_string()),
+            vesting_amount: Uint128::from(100u32),
+            prevesting_amount: Uint128::zero(),
+            start_time: Timestamp::from_nanos(100),
+            end_time: Timestamp::from_nanos(200),
+            cliff_time: None,
+        };
+        let info = mock_info("addr0001", &[]);
+        let err = execute(deps.as_mut(), env.clone(), info, msg).unwrap_err();
+        assert_eq!(err, StdError::generic_err("insufficient unallocated funds"));
+
+        // only the configured token is accepted
+        let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "addr0001".to_string(),
+            amount: Uint128::from(150u32),
+            msg: to_binary(&register).unwrap(),
+        });
+        let info = mock_info("token0002", &[]);
+        let err = execute(deps.as_mut(), env.clone(), info, msg.clone()).unwrap_err();
+        assert_eq!(err, StdError::generic_err("unauthorized"));
+
+        // and registrations on behalf of the owner only
+        let other_msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "addr0003".to_string(),
+            amount: Uint128::from(150u32),
+            msg: to_binary(&register).unwrap(),
+        });
+        let info = mock_info("token0001", &[]);
+        let err = execute(deps.as_mut(), env.clone(), info, other_msg).unwrap_err();
+        assert_eq!(err, StdError::generic_err("unauthorized"));
+
+        // funded registration keeps the rest unallocated
+        let info = mock_info("token0001", &[]);
+        let _ = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::Unallocated {}).unwrap();
+        let unallocated: UnallocatedResponse = from_binary(&res).unwrap();
+        assert_eq!(unallocated.unallocated_amount, Uint128::from(50u32));
+
+        // which can't cover another 100
+        let msg = ExecuteMsg::RegisterVestingAccount {
+            address: Addr::unchecked("addr0003".to_string()),
+            vesting_amount: Uint128::from(100u32),
+            prevesting_amount: Uint128::zero(),
+            start_time: Timestamp::from_nanos(100),
+            end_time: Timestamp::from_nanos(200),
+            cliff_time: None,
+        };
+        let info = mock_info("addr0001", &[]);
+        let err = execute(deps.as_mut(), env.clone(), info.clone(), msg.clone()).unwrap_err();
+        assert_eq!(err, StdError::generic_err("insufficient unallocated funds"));
+
+        // until funded
+        fund(deps.as_mut(), env.clone(), 50);
+        let _ = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::Unallocated {}).unwrap();
+        let unallocated: UnallocatedResponse = from_binary(&res).unwrap();
+        assert_eq!(unallocated.unallocated_amount, Uint128::zero());
+    }
+
+    #[test]
+    fn withdraw_unallocated() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg {
+            token_address: Addr::unchecked("token0001".to_string()),
+            owner_address: Some(Addr::unchecked("addr0001".to_string())),
+        };
+
+        let mut env = mock_env();
+        env.block.time = Timestamp::from_nanos(0);
+        let info = mock_info("addr0000", &[]);
+        let _res = instantiate(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+        let info = mock_info("addr0001", &[]);
+        fund(deps.as_mut(), env.clone(), 150);
+        let msg = ExecuteMsg::RegisterVestingAccount {
+            address: Addr::unchecked("addr0002".to_string()),
+            vesting_amount: Uint128::from(100u32),
+            prevesting_amount: Uint128::zero(),
+            start_time: Timestamp::from_nanos(100),
+            end_time: Timestamp::from_nanos(200),
+            cliff_time: None,
+        };
+        let _ = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+        let unallocated = |deps: Deps, env: Env| -> Uint128 {
+            let res = query(deps, env, QueryMsg::Unallocated {}).unwrap();
+            from_binary::<UnallocatedResponse>(&res).unwrap().unallocated_amount
+        };
+
+        // the unvested amount of a deregistered schedule stays unallocated
+        env.block.height += 1;
+        env.block.time = Timestamp::from_nanos(150);
+        let msg = ExecuteMsg::DeregisterVestingAccount {
+            address: Addr::unchecked("addr0002".to_string()),
+            schedule_id: 0,
+            vested_token_recipient: None,
+            left_vesting_token_recipient: None,
+        };
+        let res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        assert_eq!(res.messages.len(), 1);
+        assert_eq!(unallocated(deps.as_ref(), env.clone()), Uint128::from(100u32));
+
+        // only the owner withdraws it, up to the unallocated amount
+        let msg = ExecuteMsg::WithdrawUnallocated {
+            amount: Uint128::from(100u32),
+            recipient: Some(Addr::unchecked("addr0003".to_string())),
+        };
+        let err = execute(deps.as_mut(), env.clone(), mock_info("addr0003", &[]), msg.clone()).unwrap_err();
+        assert_eq!(err, StdError::generic_err("unauthorized"));
+        let too_much = ExecuteMsg::WithdrawUnallocated {
+            amount: Uint128::from(101u32),
+            recipient: None,
+        };
+        let err = execute(deps.as_mut(), env.clone(), info.clone(), too_much).unwrap_err();
+        assert_eq!(err, StdError::generic_err("insufficient unallocated funds"));
+
+        let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+        assert_eq!(res.messages, vec![SubMsg::new(WasmMsg::Execute {
+            contract_addr: "token0001".to_string(),
+            funds: vec![],
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: "addr0003".to_string(),
+                amount: Uint128::from(100u32),
+            }).unwrap(),
+        })]);
+        assert_eq!(unallocated(deps.as_ref(), env), Uint128::zero());
+    }
+
     #[test]
     pub fn test_migrate_update_version() {
         let mut deps = mock_dependencies();
         cw2::set_contract_version(&mut deps.storage, "my-contract", "old-version").unwrap();
+        TOKEN_ADDRESS.save(&mut deps.storage, &Addr::unchecked("token0001")).unwrap();
+        let account = Account {
+            address: Addr::unchecked("addr0002"),
+            schedule_id: 0,
+            prevesting_amount: Uint128::zero(),
+            vesting_amount: Uint128::new(60),
+            claimed_amount: Uint128::new(20),
+            registration_time: Timestamp::from_nanos(0),
+            start_time: Timestamp::from_nanos(100),
+            end_time: Timestamp::from_nanos(200),
+            cliff_time: None,
+        };
+        LEGACY_ACCOUNTS.save(&mut deps.storage, &account.address, &account).unwrap();
+        let legacy_data = VestingData {
+            vesting_amount: Uint128::new(60),
+            prevested_amount: Uint128::new(60),
+            vested_amount: Uint128::new(30),
+            claimable_amount: Uint128::new(10),
+            claimed_amount: Uint128::new(20),
+            start_time: Timestamp::from_nanos(100),
+            end_time: Timestamp::from_nanos(200),
+            ..VestingData::default()
+        };
+        LEGACY_VESTING_DATA.save(&mut deps.storage, &account.address, &legacy_data, 12000).unwrap();
+        let legacy_total = TotalVestingInfo {
+            prevested_amount: Uint128::new(60),
+            vesting_amount: Uint128::new(60),
+            vested_amount: Uint128::new(30),
+            claimed_amount: Uint128::new(20),
+            ..TotalVestingInfo::default()
+        };
+        LEGACY_VESTING_TOTAL.save(&mut deps.storage, &legacy_total, 12000).unwrap();
+        deps.querier.update_wasm(|query| match query {
+            WasmQuery::Smart { .. } => SystemResult::Ok(ContractResult::Ok(
+                to_binary(&BalanceResponse { balance: Uint128::new(100) }).unwrap(),
+            )),
+            _ => unimplemented!(),
+        });
         migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
         let version = cw2::get_contract_version(&deps.storage).unwrap();
         assert_eq!(version.version, CONTRACT_VERSION);
         assert_eq!(version.contract, CONTRACT_NAME);
+
+        // the account becomes the first schedule of its address
+        assert_eq!(LEGACY_ACCOUNTS.may_load(&deps.storage, &account.address).unwrap(), None);
+        assert_eq!(ACCOUNTS.load(&deps.storage, (&account.address, 0)).unwrap(), account);
+        assert_eq!(NEXT_SCHEDULE_ID.load(&deps.storage, &account.address).unwrap(), 1);
+
+        // balance of a contract funded by transfers and not owed to accounts becomes unallocated
+        let unallocated = UNALLOCATED.load(&deps.storage).unwrap();
+        assert_eq!(unallocated, Uint128::new(60));
+
+        // heights before the migration keep their snapshots
+        let env = mock_env();
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::VestingAccount {
+            address: account.address.clone(), height: Some(12001),
+        }).unwrap();
+        assert_eq!(from_binary::<VestingAccountResponse>(&res).unwrap().vestings, legacy_data);
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::VestingTotal { height: Some(env.block.height) }).unwrap();
+        assert_eq!(from_binary::<VestingTotalResponse>(&res).unwrap().info, legacy_total);
+
+        // later heights are computed from the schedules
+        let mut env = env;
+        env.block.height += 1;
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::VestingAccount {
+            address: account.address.clone(), height: None,
+        }).unwrap();
+        let vestings = from_binary::<VestingAccountResponse>(&res).unwrap().vestings;
+        assert_eq!(vestings.vested_amount, Uint128::new(60));
+        assert_eq!(vestings.claimable_amount, Uint128::new(40));
+        let res = query(deps.as_ref(), env, QueryMsg::VestingTotal { height: None }).unwrap();
+        assert_eq!(from_binary::<VestingTotalResponse>(&res).unwrap().info.vested_amount, Uint128::new(60));
     }
 }
@@ -2,7 +2,7 @@ use cosmwasm_std::{Addr, BlockInfo, coin, CosmosMsg, Empty, Timestamp, to_binary
 use cw20::{BalanceResponse, Cw20Coin, Cw20ExecuteMsg, Cw20QueryMsg};
 use cw_multi_test::{App, Contract, ContractWrapper, Executor, next_block};
 
-use crate::{contract, msg::{ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg, VestingAccountResponse}, state::VestingData};
+use crate::{contract, msg::{ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg, ReceiveMsg, VestingAccountResponse}, state::VestingData};
 use crate::msg::InfoResponse;
 
 const OWNER: &str = "owner0000";
@@ -97,13 +97,21 @@ fn simple_e2e_test() {
 
     let initial_block_time = app.block_info().time;
 
-    // move kleo on vesting contract
+    // move kleo on vesting contract and register vesting account
     let _ = app.execute_contract(
         Addr::unchecked(OWNER.to_string()),
         cw20_contract_addr.clone(),
-        &Cw20ExecuteMsg::Transfer {
-            recipient: vesting_contract_addr.clone().into_string(),
+        &Cw20ExecuteMsg::Send {
+            contract: vesting_contract_addr.clone().into_string(),
             amount: Uint128::new(10_000_000u128),
+            msg: to_binary(&ReceiveMsg::RegisterVestingAccount {
+                address: Addr::unchecked(USER1.to_string()),
+                vesting_amount: Uint128::new(10_000_000),
+                prevesting_amount: Uint128::new(1_000_000),
+                start_time: initial_block_time,
+                end_time: initial_block_time.plus_seconds(100),
+                cliff_time: None,
+            }).unwrap(),
         },
         &vec![],
     );
@@ -114,20 +122,6 @@ fn simple_e2e_test() {
     let vesting_balance = query_cw20_balance(&app, cw20_contract_addr.clone(), vesting_contract_addr.clone());
     assert_eq!(Uint128::new(10_000_000u128), vesting_balance);
 
-    // register vesting account
-    let _ = app.execute_contract(
-        Addr::unchecked(OWNER.to_string()),
-        vesting_contract_addr.clone(),
-        &ExecuteMsg::RegisterVestingAccount {
-            address: Addr::unchecked(USER1.to_string()),
-            vesting_amount: Uint128::new(10_000_000),
-            prevesting_amount: Uint128::new(1_000_000),
-            start_time: initial_block_time,
-            end_time: initial_block_time.plus_seconds(100),
-        },
-        &vec![],
-    );
-
     // 5seconds more
     app.update_block(next_block);
 
@@ -159,6 +153,7 @@ fn simple_e2e_test() {
                 registration_time: initial_block_time,
                 start_time: initial_block_time,
                 end_time: initial_block_time.plus_seconds(100u64),
+                cliff_time: None,
             }
         },
         res
@@ -194,12 +189,13 @@ fn simple_e2e_test() {
                 prevesting_amount: Uint128::new(1_000_000u128),
                 prevested_amount: Uint128::new(10_000_000u128),
                 vesting_amount: Uint128::new(10_000_000u128),
-                vested_amount: Uint128::new(500_000u128),
-                claimable_amount: Uint128::new(0),
+                vested_amount: Uint128::new(1_000_000u128),
+                claimable_amount: Uint128::new(500_000u128),
                 claimed_amount: Uint128::new(500_000u128),
                 registration_time: initial_block_time,
                 start_time: initial_block_time,
                 end_time: initial_block_time.plus_seconds(100u64),
+                cliff_time: None,
             }
         },
         res
@@ -235,6 +231,7 @@ fn simple_e2e_test() {
                 registration_time: initial_block_time,
                 start_time: initial_block_time,
                 end_time: initial_block_time.plus_seconds(100u64),
+                cliff_time: None,
             }
         },
         res
@@ -251,6 +248,7 @@ fn simple_e2e_test() {
         vesting_contract_addr.clone(),
         &ExecuteMsg::DeregisterVestingAccount {
             address: Addr::unchecked(USER1.to_string()),
+            schedule_id: 0,
             vested_token_recipient: Some(Addr::unchecked(USER1.to_string())),
             left_vesting_token_recipient: Some(Addr::unchecked(OWNER.to_string())),
         },
@@ -303,6 +301,7 @@ fn to_json() {
         prevesting_amount: Uint128::new(30_000_000_000),
         start_time: Timestamp::from_nanos(1669466438268000000),
         end_time: Timestamp::from_nanos(1669725638268000000),
+        cliff_time: None,
     };
     println!("{}", serde_json::to_string(&msg).unwrap());
 }
@@ -1,5 +1,6 @@
 use cosmwasm_std::{Addr, Timestamp, Uint128};
 use cw2::ContractVersion;
+use cw20::Cw20ReceiveMsg;
 use proposal_hooks::ProposalHookMsg;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
@@ -24,17 +25,42 @@ pub enum ExecuteMsg {
         prevesting_amount: Uint128,
         start_time: Timestamp,
         end_time: Timestamp,
+        // nothing vests before the cliff, the amount vested since the start unlocks at it
+        cliff_time: Option<Timestamp>,
     },
     DeregisterVestingAccount {
         address: Addr,
+        schedule_id: u64,
         vested_token_recipient: Option<Addr>,
+        // the unvested amount stays unallocated if not set
         left_vesting_token_recipient: Option<Addr>,
     },
     Claim {
         recipient: Option<Addr>,
     },
+    // withdraws received tokens not registered to a vesting account, to the owner by default
+    WithdrawUnallocated {
+        amount: Uint128,
+        recipient: Option<Addr>,
+    },
     Snapshot {},
     ProposalHook(ProposalHookMsg),
+    Receive(Cw20ReceiveMsg),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReceiveMsg {
+    Fund {},
+    RegisterVestingAccount {
+        address: Addr,
+        vesting_amount: Uint128,
+        prevesting_amount: Uint128,
+        start_time: Timestamp,
+        end_time: Timestamp,
+        // nothing vests before the cliff, the amount vested since the start unlocks at it
+        cliff_time: Option<Timestamp>,
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -42,14 +68,19 @@ pub enum ExecuteMsg {
 pub enum QueryMsg {
     OwnerAddress {},
     TokenAddress {},
+    // sum of all the address' vesting schedules
     VestingAccount {
         address: Addr,
         height: Option<u64>,
     },
+    VestingSchedules {
+        address: Addr,
+    },
     VestingTotal {
         height: Option<u64>,
     },
     Info {},
+    Unallocated {},
 }
 
 #[derive(Serialize, Deserialize, JsonSchema, PartialEq, Debug)]
@@ -68,11 +99,28 @@ pub struct VestingAccountResponse {
     pub vestings: VestingData,
 }
 
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Debug)]
+pub struct VestingSchedule {
+    pub schedule_id: u64,
+    pub vestings: VestingData,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Debug)]
+pub struct VestingSchedulesResponse {
+    pub address: Addr,
+    pub schedules: Vec<VestingSchedule>,
+}
+
 #[derive(Serialize, Deserialize, JsonSchema, PartialEq, Debug)]
 pub struct VestingTotalResponse {
     pub info: TotalVestingInfo,
 }
 
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Debug)]
+pub struct UnallocatedResponse {
+    pub unallocated_amount: Uint128,
+}
+
 #[derive(Serialize, Deserialize, JsonSchema, PartialEq, Debug)]
 pub struct InfoResponse {
     pub info: ContractVersion,
@@ -1,27 +1,52 @@
-use cosmwasm_std::{Addr, BlockInfo, StdError, StdResult, Timestamp, Uint128};
+use std::convert::TryFrom;
+
+use cosmwasm_std::{Addr, BlockInfo, Decimal256, StdError, StdResult, Timestamp, Uint128, Uint256};
 use cw_storage_plus::{Item, Map, SnapshotItem, SnapshotMap, Strategy};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-pub const ACCOUNTS: Map<&Addr, Account> = Map::new(
-    "vesting_account",
+// vesting schedules by beneficiary and schedule id
+pub const ACCOUNTS: SnapshotMap<(&Addr, u64), Account> = SnapshotMap::new(
+    "vesting_schedule",
+    "vesting_schedule__checkpoints",
+    "vesting_schedule__changelog",
+    Strategy::EveryBlock,
+);
+// accounts keyed by beneficiary only, before schedules, moved to `ACCOUNTS` on migration
+pub const LEGACY_ACCOUNTS: Map<&Addr, Account> = Map::new("vesting_account");
+// schedule id given to the next schedule registered for a beneficiary
+pub const NEXT_SCHEDULE_ID: Map<&Addr, u64> = Map::new("next_schedule_id");
+
+pub const VESTING_TOTALS: SnapshotItem<VestingTotals> = SnapshotItem::new(
+    "vesting_totals",
+    "vesting_totals__checkpoints",
+    "vesting_totals__changelog",
+    Strategy::EveryBlock,
 );
 
-pub const VESTING_DATA: SnapshotMap<&Addr, VestingData> = SnapshotMap::new(
+// snapshots of every account and of the totals taken before totals were computed from curves,
+// they answer queries for heights up to `MIGRATION_HEIGHT`
+pub const LEGACY_VESTING_DATA: SnapshotMap<&Addr, VestingData> = SnapshotMap::new(
     "vesting_data",
     "vesting_data__checkpoints",
     "vesting_data__changelog",
     Strategy::EveryBlock,
 );
-
-pub const VESTING_TOTAL: SnapshotItem<TotalVestingInfo> = SnapshotItem::new(
+pub const LEGACY_VESTING_TOTAL: SnapshotItem<TotalVestingInfo> = SnapshotItem::new(
     "vesting_total",
     "vesting_total__checkpoints",
     "vesting_total__changelog",
     Strategy::EveryBlock,
 );
+// height of the migration that built `VESTING_TOTALS`
+pub const MIGRATION_HEIGHT: Item<u64> = Item::new("migration_height");
+
+// times in nanoseconds at which a schedule's curves change, see `Account::breakpoints`
+pub const BREAKPOINTS: Map<(u64, (&Addr, u64)), bool> = Map::new("breakpoints");
 pub const TOKEN_ADDRESS: Item<Addr> = Item::new("token_address");
 pub const OWNER_ADDRESS: Item<Addr> = Item::new("owner_address");
+// tokens received and not yet registered to a vesting account
+pub const UNALLOCATED: Item<Uint128> = Item::new("unallocated");
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug, Default)]
 pub struct TotalVestingInfo {
@@ -32,15 +57,87 @@ pub struct TotalVestingInfo {
     pub claimed_amount: Uint128,
 }
 
+/// Sum of piecewise linear amounts, `amount + rate * t - offset` at time t in nanoseconds.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug, Default)]
+pub struct Curve {
+    pub amount: Uint128,
+    pub rate: Decimal256,
+    pub offset: Decimal256,
+}
+
+impl Curve {
+    fn linear(amount: Uint128, from: Timestamp, to: Timestamp) -> Curve {
+        let rate = Decimal256::from_ratio(amount, to.nanos() - from.nanos());
+        Curve {
+            amount: Uint128::zero(),
+            rate,
+            offset: rate * Decimal256::from_ratio(from.nanos(), 1u64),
+        }
+    }
+
+    fn constant(amount: Uint128) -> Curve {
+        Curve {
+            amount,
+            ..Curve::default()
+        }
+    }
+
+    pub fn add(&mut self, other: &Curve) {
+        self.amount += other.amount;
+        self.rate += other.rate;
+        self.offset += other.offset;
+    }
+
+    pub fn sub(&mut self, other: &Curve) {
+        self.amount -= other.amount;
+        self.rate -= other.rate;
+        self.offset -= other.offset;
+    }
+
+    pub fn at(&self, time: Timestamp) -> StdResult<Uint128> {
+        let linear = self.rate * Decimal256::from_ratio(time.nanos(), 1u64) - self.offset;
+        let linear = Uint128::try_from(linear.atomics() / Decimal256::one().atomics())?;
+        Ok(self.amount + linear)
+    }
+}
+
+/// Aggregates of all vesting accounts. Every write brings the curves towards the block time in
+/// bounded steps, accounts changing phase after `time` are applied from `BREAKPOINTS` when evaluating them.
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug, Default)]
+pub struct VestingTotals {
+    pub time: Timestamp,
+    pub prevesting_amount: Uint128,
+    pub claimed_amount: Uint128,
+    pub prevested: Curve,
+    pub vested: Curve,
+}
+
+impl VestingTotals {
+    pub fn info(&self, time: Timestamp) -> StdResult<TotalVestingInfo> {
+        let prevested_amount = self.prevested.at(time)?;
+        Ok(TotalVestingInfo {
+            prevesting_amount: self.prevesting_amount,
+            prevested_amount,
+            vesting_amount: prevested_amount,
+            vested_amount: self.vested.at(time)?,
+            claimed_amount: self.claimed_amount,
+        })
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
 pub struct Account {
     pub address: Addr,
+    #[serde(default)]
+    pub schedule_id: u64,
     pub prevesting_amount: Uint128,
     pub vesting_amount: Uint128,
     pub claimed_amount: Uint128,
     pub registration_time: Timestamp,
     pub start_time: Timestamp,
     pub end_time: Timestamp,
+    #[serde(default)]
+    pub cliff_time: Option<Timestamp>,
 }
 
 #[derive(Serialize, Deserialize, JsonSchema, Clone, PartialEq, Debug)]
@@ -54,6 +151,8 @@ pub struct VestingData {
     pub registration_time: Timestamp,
     pub start_time: Timestamp,
     pub end_time: Timestamp,
+    #[serde(default)]
+    pub cliff_time: Option<Timestamp>,
 }
 
 impl Default for VestingData {
@@ -68,6 +167,7 @@ impl Default for VestingData {
             registration_time: Timestamp::default(),
             start_time: Timestamp::default(),
             end_time: Timestamp::default(),
+            cliff_time: None,
         }
     }
 }
@@ -99,11 +199,68 @@ impl Account {
             return Err(StdError::generic_err("assert(end_time >= start_time)"));
         }
 
+        if self.prevesting_amount > self.vesting_amount {
+            return Err(StdError::generic_err("assert(prevesting_amount <= vesting_amount)"));
+        }
+
+        if let Some(cliff_time) = self.cliff_time {
+            if cliff_time < self.start_time || cliff_time > self.end_time {
+                return Err(StdError::generic_err("assert(start_time <= cliff_time <= end_time)"));
+            }
+        }
+
         Ok(())
     }
 
+    /// Time the linearly prevested amount reaches the prevesting amount.
+    fn prevesting_end(&self) -> StdResult<Timestamp> {
+        let duration = Uint256::from(self.start_time.nanos() - self.registration_time.nanos());
+        let vesting_amount = Uint256::from(self.vesting_amount);
+        let prevesting = (Uint256::from(self.prevesting_amount) * duration + vesting_amount - Uint256::from(1u8)) / vesting_amount;
+        Ok(self.registration_time.plus_nanos(u128::from(Uint128::try_from(prevesting)?) as u64))
+    }
+
+    /// Times from registration on at which the account's prevested and vested curves change.
+    pub fn breakpoints(&self) -> StdResult<Vec<Timestamp>> {
+        let mut breakpoints = vec![self.registration_time, self.prevesting_end()?, self.start_time];
+        breakpoints.extend(self.cliff_time);
+        breakpoints.push(self.end_time);
+        breakpoints.dedup();
+        Ok(breakpoints)
+    }
+
+    /// Prevested and vested curves of the account at `time`, zero before registration. Totals
+    /// add these up, so they match the sum of the accounts' amounts up to rounding.
+    pub fn curves(&self, time: Timestamp) -> StdResult<(Curve, Curve)> {
+        if time < self.registration_time {
+            return Ok((Curve::default(), Curve::default()));
+        }
+
+        let prevested = if time >= self.start_time {
+            Curve::constant(self.vesting_amount)
+        } else if time >= self.prevesting_end()? {
+            Curve::linear(self.vesting_amount, self.registration_time, self.start_time)
+        } else {
+            Curve::constant(self.prevesting_amount)
+        };
+
+        let vested = if time >= self.end_time {
+            Curve::constant(self.vesting_amount)
+        } else if time >= self.start_time && !self.before_cliff(time) {
+            Curve::linear(self.vesting_amount, self.start_time, self.end_time)
+        } else {
+            Curve::default()
+        };
+
+        Ok((prevested, vested))
+    }
+
+    fn before_cliff(&self, time: Timestamp) -> bool {
+        matches!(self.cliff_time, Some(cliff_time) if time < cliff_time)
+    }
+
     pub fn vested_amount(&self, block_info: &BlockInfo) -> StdResult<Uint128> {
-        if block_info.time < self.start_time {
+        if block_info.time < self.start_time || self.before_cliff(block_info.time) {
             return Ok(Uint128::zero());
         }
 
@@ -150,12 +307,14 @@ mod test {
     fn test_vested_amount() {
         let account = Account {
             address: Addr::unchecked("addr00001".to_string()),
+            schedule_id: 0,
             prevesting_amount: Uint128::new(10u128),
             vesting_amount: Uint128::new(100u128),
             claimed_amount: Uint128::zero(),
             registration_time: Timestamp::from_nanos(0),
             start_time: Timestamp::from_nanos(50),
             end_time: Timestamp::from_nanos(100),
+            cliff_time: None,
         };
 
         // Check fixed vesting before start time
@@ -195,6 +354,41 @@ mod test {
         assert_eq!(prevested_tokens, Uint128::new(100));
     }
 
+    #[test]
+    fn test_vested_amount_cliff() {
+        let account = Account {
+            address: Addr::unchecked("addr00001".to_string()),
+            schedule_id: 0,
+            prevesting_amount: Uint128::zero(),
+            vesting_amount: Uint128::new(100u128),
+            claimed_amount: Uint128::zero(),
+            registration_time: Timestamp::from_nanos(0),
+            start_time: Timestamp::from_nanos(50),
+            end_time: Timestamp::from_nanos(150),
+            cliff_time: Some(Timestamp::from_nanos(75)),
+        };
+
+        // Check nothing vested before the cliff
+        let mut env = mock_env();
+        env.block.time = Timestamp::from_nanos(74);
+        assert_eq!(account.vested_amount(&env.block).unwrap(), Uint128::new(0));
+
+        // Check the amount accrued since start time unlocks at the cliff
+        env.block.time = Timestamp::from_nanos(75);
+        assert_eq!(account.vested_amount(&env.block).unwrap(), Uint128::new(25));
+
+        env.block.time = Timestamp::from_nanos(100);
+        assert_eq!(account.vested_amount(&env.block).unwrap(), Uint128::new(50));
+
+        // Check the cliff lies within the vesting period
+        env.block.time = Timestamp::from_nanos(0);
+        let account = Account {
+            cliff_time: Some(Timestamp::from_nanos(151)),
+            ..account
+        };
+        assert!(account.validate(&env.block).is_err());
+    }
+
 }
 
 pub fn get_vesting_data_from_account(account: Account, block_info: &BlockInfo) -> StdResult<VestingData> {
@@ -212,6 +406,7 @@ pub fn get_vesting_data_from_account(account: Account, block_info: &BlockInfo) -
         registration_time: account.registration_time,
         start_time: account.start_time,
         end_time: account.end_time,
+        cliff_time: account.cliff_time,
         claimable_amount: claimable_amount,
         claimed_amount: account.claimed_amount,
     };
@@ -353,12 +353,9 @@ pub fn query_voting_power_at_height(
     )?;
     let staking_balance = staking_res.balance;
     //let vesting_balance = vesting_res.vestings.claimable_amount;
-    let vesting_balance;
-    if vesting_res.vestings.vesting_amount > vesting_res.vestings.prevested_amount {
-        vesting_balance = vesting_res.vestings.prevested_amount;
-    } else {
-        vesting_balance = vesting_res.vestings.vesting_amount.checked_sub(vesting_res.vestings.claimed_amount).unwrap_or(Uint128::zero());
-    }
+    // nothing is claimed before a schedule is fully prevested, so summed schedules
+    // count their prevested amount before the start and their unclaimed amount after it
+    let vesting_balance = vesting_res.vestings.prevested_amount.checked_sub(vesting_res.vestings.claimed_amount).unwrap_or(Uint128::zero());
 
     let power = staking_balance.checked_add(vesting_balance).unwrap_or(Uint128::zero());
     to_binary(&cw_core_interface::voting::VotingPowerAtHeightResponse {
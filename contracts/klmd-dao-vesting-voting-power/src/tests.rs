@@ -77,24 +77,21 @@ fn stake_tokens(app: &mut App, staking_addr: Addr, cw20_addr: Addr, sender: &str
         .unwrap();
 }
 
-fn send_token(app: &mut App, receiver: &str, cw20_addr: Addr, sender: &str, amount: u128) {
-    let msg = cw20::Cw20ExecuteMsg::Transfer {
-        amount: Uint128::new(amount),
-        recipient: receiver.to_string(),
-    };
-    app.execute_contract(Addr::unchecked(sender), cw20_addr, &msg, &[])
-        .unwrap();
-}
-
-fn register_vesting_account(app: &mut App, account_addr: Addr, vesting_addr: Addr, sender: &str, prevesting_amount: Uint128, amount: Uint128, start_time: Timestamp, end_time: Timestamp) {
-    let msg = klmd_custom_vesting::msg::ExecuteMsg::RegisterVestingAccount {
+fn register_vesting_account(app: &mut App, account_addr: Addr, vesting_addr: Addr, cw20_addr: Addr, sender: &str, prevesting_amount: Uint128, amount: Uint128, start_time: Timestamp, end_time: Timestamp) {
+    let msg = klmd_custom_vesting::msg::ReceiveMsg::RegisterVestingAccount {
         address: account_addr.clone(),
         vesting_amount: amount,
         prevesting_amount: prevesting_amount,
         start_time,
         end_time,
+        cliff_time: None,
     };
-    app.execute_contract(Addr::unchecked(sender), vesting_addr, &msg, &[])
+    let msg = cw20::Cw20ExecuteMsg::Send {
+        contract: vesting_addr.to_string(),
+        amount,
+        msg: to_binary(&msg).unwrap(),
+    };
+    app.execute_contract(Addr::unchecked(sender), cw20_addr, &msg, &[])
         .unwrap();
 }
 
@@ -361,7 +358,7 @@ fn test_new_cw20() {
     );
 
     // Stake 1 token as creator
-    stake_tokens(&mut app, staking_addr, token_addr, CREATOR_ADDR, 1);
+    stake_tokens(&mut app, staking_addr, token_addr.clone(), CREATOR_ADDR, 1);
     app.update_block(next_block);
 
     // Expect 1 as creator has now staked 1 and not vested.
@@ -404,6 +401,7 @@ fn test_new_cw20() {
         &mut app,
         Addr::unchecked("creator"),
         vesting_addr.clone(),
+        token_addr.clone(),
         "dao",
         Uint128::zero(),
         Uint128::new(1u128),
@@ -731,7 +729,7 @@ fn test_existing_cw20_new_staking_existing_vesting() {
     );
 
     // Stake 1 token as creator
-    stake_tokens(&mut app, staking_addr, token_addr, CREATOR_ADDR, 1);
+    stake_tokens(&mut app, staking_addr, token_addr.clone(), CREATOR_ADDR, 1);
     app.update_block(next_block);
 
     // Expect 1 as creator has now staked 1
@@ -774,6 +772,7 @@ fn test_existing_cw20_new_staking_existing_vesting() {
         &mut app,
         Addr::unchecked(CREATOR_ADDR),
         vesting_addr.clone(),
+        token_addr.clone(),
         CREATOR_ADDR,
         Uint128::zero(),
         Uint128::new(1),
@@ -1871,8 +1870,6 @@ pub fn test_contracts_integration_init() {
     app.execute_contract(Addr::unchecked(dao_core_addr), proposal_addr[0].clone(), &proposal_hook_msg, &[])
         .unwrap();
 
-    send_token(&mut app, vesting_addr.clone().as_str(), cw20_addr.clone(), CREATOR_ADDR, 100u128);
-
     assert_eq!(voting_addr, Addr::unchecked("contract1"));
     assert_eq!(cw20_addr, Addr::unchecked("contract2"));
     assert_eq!(staking_addr, Addr::unchecked("contract3"));
@@ -1898,6 +1895,7 @@ pub fn test_contracts_integration_init() {
         &mut app,
         Addr::unchecked(CREATOR_ADDR),
         vesting_addr.clone(),
+        cw20_addr.clone(),
         CREATOR_ADDR,
         Uint128::new(10),
         Uint128::new(100),
@@ -1944,6 +1942,7 @@ pub fn test_contracts_integration_init() {
                 registration_time: time_zero,
                 start_time: time_zero.plus_seconds(5),
                 end_time: time_zero.plus_seconds(105),
+                cliff_time: None,
             }
         }
     );
@@ -1986,6 +1985,7 @@ pub fn test_contracts_integration_init() {
                 registration_time: time_zero,
                 start_time: time_zero.plus_seconds(5),
                 end_time: time_zero.plus_seconds(105),
+                cliff_time: None,
             }
         }
     );
@@ -2028,6 +2028,7 @@ pub fn test_contracts_integration_init() {
                 registration_time: time_zero,
                 start_time: time_zero.plus_seconds(5),
                 end_time: time_zero.plus_seconds(105),
+                cliff_time: None,
             }
         }
     );
@@ -0,0 +1,346 @@
+//! Merkle trees for the cw20-merkle-airdrop contract.
+//!
+//! Leaves are `sha256(address + amount)` with the amount in decimal, pairs are hashed in sorted
+//! order and an odd node is promoted to the next layer as is. This matches the claim
+//! verification of the contract and the merkletreejs `sort: true` trees of the js helpers.
+//! Stages with indexed leaves hash `index:address amount` instead, see [`assign_indexes`].
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeSet;
+use thiserror::Error;
+
+pub type Hash = [u8; 32];
+
+#[derive(Error, Debug, PartialEq)]
+pub enum TreeError {
+    #[error("Airdrop list is empty")]
+    Empty {},
+
+    #[error("Address {address} is listed more than once")]
+    DuplicateAddress { address: String },
+
+    #[error("Invalid amount {amount} for {address}")]
+    InvalidAmount { address: String, amount: String },
+
+    #[error("Invalid CSV line {line}: {content}")]
+    InvalidCsv { line: usize, content: String },
+
+    #[error("Invalid JSON: {0}")]
+    InvalidJson(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Account {
+    pub address: String,
+    pub amount: u128,
+    /// Index of the leaf, for stages with indexed leaves.
+    pub index: Option<u64>,
+}
+
+impl Account {
+    /// Parses the amount, which must be a positive integer.
+    pub fn new(address: &str, amount: &str) -> Result<Self, TreeError> {
+        let invalid = || TreeError::InvalidAmount {
+            address: address.to_string(),
+            amount: amount.to_string(),
+        };
+        let parsed: u128 = amount.parse().map_err(|_| invalid())?;
+        if parsed == 0 {
+            return Err(invalid());
+        }
+        Ok(Account {
+            address: address.to_string(),
+            amount: parsed,
+            index: None,
+        })
+    }
+
+    pub fn leaf(&self) -> Hash {
+        match self.index {
+            Some(index) => indexed_leaf(index, &self.address, self.amount),
+            None => leaf(&self.address, self.amount),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ListEntry {
+    address: String,
+    amount: String,
+}
+
+/// Parses the airdrop file format of the js helpers,
+/// `[{"address": "juno1...", "amount": "100"}, ...]`.
+pub fn parse_json(input: &str) -> Result<Vec<Account>, TreeError> {
+    let entries: Vec<ListEntry> =
+        serde_json::from_str(input).map_err(|e| TreeError::InvalidJson(e.to_string()))?;
+    entries
+        .iter()
+        .map(|e| Account::new(&e.address, &e.amount))
+        .collect()
+}
+
+/// Parses `address,amount` lines. A leading `address,amount` header and empty lines are
+/// skipped.
+pub fn parse_csv(input: &str) -> Result<Vec<Account>, TreeError> {
+    let mut accounts = vec![];
+    for (i, content) in input.lines().enumerate() {
+        let content = content.trim();
+        if content.is_empty() || (accounts.is_empty() && content == "address,amount") {
+            continue;
+        }
+        let account = match content.split(',').collect::<Vec<_>>()[..] {
+            [address, amount] => Account::new(address.trim(), amount.trim())?,
+            _ => {
+                return Err(TreeError::InvalidCsv {
+                    line: i + 1,
+                    content: content.to_string(),
+                })
+            }
+        };
+        accounts.push(account);
+    }
+    Ok(accounts)
+}
+
+/// Numbers the accounts in list order, for a stage registered with `indexed_leaves`.
+pub fn assign_indexes(accounts: &mut [Account]) {
+    for (index, account) in accounts.iter_mut().enumerate() {
+        account.index = Some(index as u64);
+    }
+}
+
+/// Proof of an account, in the format of the contract's test data.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct AccountProof {
+    pub account: String,
+    pub amount: String,
+    /// Root is the hex-encoded merkle root.
+    pub root: String,
+    /// Proofs are the hex-encoded merkle proof, as passed to `Claim`.
+    pub proofs: Vec<String>,
+    /// Index is the `leaf_index` of indexed leaves.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub index: Option<u64>,
+}
+
+pub struct MerkleTree {
+    /// Layers from the sorted leaves up to the root.
+    layers: Vec<Vec<Hash>>,
+}
+
+impl MerkleTree {
+    pub fn new(accounts: &[Account]) -> Result<Self, TreeError> {
+        if accounts.is_empty() {
+            return Err(TreeError::Empty {});
+        }
+        let mut addresses = BTreeSet::new();
+        for account in accounts {
+            if !addresses.insert(account.address.as_str()) {
+                return Err(TreeError::DuplicateAddress {
+                    address: account.address.clone(),
+                });
+            }
+        }
+
+        let mut leaves: Vec<Hash> = accounts.iter().map(Account::leaf).collect();
+        leaves.sort_unstable();
+
+        let mut layers = vec![leaves];
+        while let Some(layer) = layers.last().filter(|l| l.len() > 1) {
+            let next = layer
+                .chunks(2)
+                .map(|pair| match pair {
+                    [a, b] => hash_pair(a, b),
+                    // odd node is promoted
+                    _ => pair[0],
+                })
+                .collect();
+            layers.push(next);
+        }
+        Ok(MerkleTree { layers })
+    }
+
+    pub fn root(&self) -> Hash {
+        self.layers[self.layers.len() - 1][0]
+    }
+
+    /// HexRoot is the `merkle_root` of `RegisterMerkleRoot`.
+    pub fn hex_root(&self) -> String {
+        hex::encode(self.root())
+    }
+
+    /// Returns the proof of the account, None if it is not in the tree.
+    pub fn proof(&self, account: &Account) -> Option<Vec<Hash>> {
+        let mut index = self.layers[0].binary_search(&account.leaf()).ok()?;
+        let mut proof = vec![];
+        for layer in &self.layers[..self.layers.len() - 1] {
+            if let Some(sibling) = layer.get(index ^ 1) {
+                proof.push(*sibling);
+            }
+            index /= 2;
+        }
+        Some(proof)
+    }
+
+    pub fn account_proof(&self, account: &Account) -> Option<AccountProof> {
+        Some(AccountProof {
+            account: account.address.clone(),
+            amount: account.amount.to_string(),
+            root: self.hex_root(),
+            proofs: self.proof(account)?.iter().map(hex::encode).collect(),
+            index: account.index,
+        })
+    }
+}
+
+pub fn leaf(address: &str, amount: u128) -> Hash {
+    Sha256::digest(format!("{}{}", address, amount).as_bytes()).into()
+}
+
+pub fn indexed_leaf(index: u64, address: &str, amount: u128) -> Hash {
+    Sha256::digest(format!("{}:{}{}", index, address, amount).as_bytes()).into()
+}
+
+pub fn hash_pair(a: &Hash, b: &Hash) -> Hash {
+    let mut hashes = [*a, *b];
+    hashes.sort_unstable();
+    Sha256::digest(&hashes.concat()).into()
+}
+
+pub fn verify(root: &Hash, leaf: Hash, proof: &[Hash]) -> bool {
+    proof.iter().fold(leaf, |hash, p| hash_pair(&hash, p)) == *root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+    use cosmwasm_std::{to_binary, Uint128};
+    use cw20::Cw20ReceiveMsg;
+    use cw20_merkle_airdrop::contract::{execute, instantiate};
+    use cw20_merkle_airdrop::msg::{ExecuteMsg, InstantiateMsg, ReceiveMsg};
+
+    const STAGE_2_LIST: &str =
+        include_str!("../../../contracts/klmd-airdrop/testdata/airdrop_stage_2_list.json");
+    const STAGE_2_TEST_DATA: &str =
+        include_str!("../../../contracts/klmd-airdrop/testdata/airdrop_stage_2_test_data.json");
+
+    #[test]
+    fn matches_js_helpers() {
+        let accounts = parse_json(STAGE_2_LIST).unwrap();
+        let tree = MerkleTree::new(&accounts).unwrap();
+        let expected: AccountProof = serde_json::from_str(STAGE_2_TEST_DATA).unwrap();
+
+        let account = Account::new(&expected.account, &expected.amount).unwrap();
+        assert_eq!(tree.account_proof(&account).unwrap(), expected);
+
+        for account in &accounts {
+            let proof = tree.proof(account).unwrap();
+            assert!(verify(&tree.root(), account.leaf(), &proof));
+        }
+        let unknown = Account::new(&expected.account, "15").unwrap();
+        assert_eq!(tree.proof(&unknown), None);
+    }
+
+    #[test]
+    fn parse_lists() {
+        let csv = "address,amount\nwasm1k9hwzxs889jpvd7env8z49gad3a3633vg350tq,100\n\n\
+                   wasm1uy9ucvgerneekxpnfwyfnpxvlsx5dzdpf0mzjd, 1010\n";
+        let json = r#"[
+            {"address": "wasm1k9hwzxs889jpvd7env8z49gad3a3633vg350tq", "amount": "100"},
+            {"address": "wasm1uy9ucvgerneekxpnfwyfnpxvlsx5dzdpf0mzjd", "amount": "1010"}
+        ]"#;
+        assert_eq!(parse_csv(csv).unwrap(), parse_json(json).unwrap());
+
+        assert_eq!(
+            parse_csv("wasm1k9hwzxs889jpvd7env8z49gad3a3633vg350tq,100,1").unwrap_err(),
+            TreeError::InvalidCsv {
+                line: 1,
+                content: "wasm1k9hwzxs889jpvd7env8z49gad3a3633vg350tq,100,1".to_string()
+            }
+        );
+        assert_eq!(
+            parse_csv("wasm1k9hwzxs889jpvd7env8z49gad3a3633vg350tq,0100.0").unwrap_err(),
+            TreeError::InvalidAmount {
+                address: "wasm1k9hwzxs889jpvd7env8z49gad3a3633vg350tq".to_string(),
+                amount: "0100.0".to_string()
+            }
+        );
+        assert!(matches!(
+            parse_json(r#"[{"address": "wasm1"}]"#).unwrap_err(),
+            TreeError::InvalidJson(_)
+        ));
+    }
+
+    #[test]
+    fn invalid_trees() {
+        assert_eq!(MerkleTree::new(&[]).err(), Some(TreeError::Empty {}));
+
+        let account = Account::new("wasm1k9hwzxs889jpvd7env8z49gad3a3633vg350tq", "100").unwrap();
+        assert_eq!(
+            MerkleTree::new(&[account.clone(), account.clone()]).err(),
+            Some(TreeError::DuplicateAddress {
+                address: account.address
+            })
+        );
+    }
+
+    #[test]
+    fn claims_verify_on_contract() {
+        for (size, indexed) in (1..=9).flat_map(|size| [(size, false), (size, true)]) {
+            let mut accounts: Vec<Account> = (0..size)
+                .map(|i| Account::new(&format!("addr{:04}", i), &(i * 100 + 7).to_string()).unwrap())
+                .collect();
+            if indexed {
+                assign_indexes(&mut accounts);
+            }
+            let tree = MerkleTree::new(&accounts).unwrap();
+            let total: u128 = accounts.iter().map(|a| a.amount).sum();
+
+            let mut deps = mock_dependencies();
+            let env = mock_env();
+            let msg = InstantiateMsg {
+                owner: Some("owner0000".to_string()),
+                cw20_token_address: "token0000".to_string(),
+            };
+            instantiate(deps.as_mut(), env.clone(), mock_info("owner0000", &[]), msg).unwrap();
+
+            let msg = ExecuteMsg::RegisterMerkleRoot {
+                merkle_root: tree.hex_root(),
+                expiration: None,
+                start: None,
+                native_denom: None,
+                cw20_token_address: None,
+                total_amount: Uint128::new(total),
+                vesting: None,
+                cross_chain: None,
+                decay: None,
+                tranches: None,
+                indexed_leaves: Some(indexed),
+                metadata: None,
+            };
+            execute(deps.as_mut(), env.clone(), mock_info("owner0000", &[]), msg).unwrap();
+            let msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+                sender: "owner0000".to_string(),
+                amount: Uint128::new(total),
+                msg: to_binary(&ReceiveMsg::FundStage { stage: 1 }).unwrap(),
+            });
+            execute(deps.as_mut(), env.clone(), mock_info("token0000", &[]), msg).unwrap();
+
+            for account in &accounts {
+                let proof = tree.account_proof(account).unwrap();
+                let msg = ExecuteMsg::Claim {
+                    stage: 1,
+                    amount: Uint128::new(account.amount),
+                    proof: proof.proofs,
+                    sig_info: None,
+                    leaf_index: proof.index,
+                };
+                let info = mock_info(&account.address, &[]);
+                execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+            }
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::process;
+
+use klmd_airdrop_tree::{assign_indexes, parse_csv, parse_json, Account, MerkleTree};
+
+const USAGE: &str = "Usage:
+  klmd-airdrop-tree root [--indexed] <list>
+  klmd-airdrop-tree proofs [--indexed] <list> <out-dir>
+
+<list> is a JSON airdrop file, [{\"address\": \"juno1...\", \"amount\": \"100\"}], or a CSV file
+of address,amount lines if it ends in .csv. proofs writes <out-dir>/<address>.json per account.
+--indexed builds the tree of a stage with indexed leaves, numbering accounts in list order.";
+
+fn main() {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let indexed = args.iter().any(|a| a == "--indexed");
+    args.retain(|a| a != "--indexed");
+    let res = match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        ["root", list] => root(list, indexed),
+        ["proofs", list, out_dir] => proofs(list, out_dir, indexed),
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+    if let Err(err) = res {
+        eprintln!("error: {}", err);
+        process::exit(1);
+    }
+}
+
+fn read_list(list: &str, indexed: bool) -> Result<Vec<Account>, Box<dyn Error>> {
+    let input = fs::read_to_string(list)?;
+    let mut accounts = if list.ends_with(".csv") {
+        parse_csv(&input)?
+    } else {
+        parse_json(&input)?
+    };
+    if indexed {
+        assign_indexes(&mut accounts);
+    }
+    Ok(accounts)
+}
+
+fn root(list: &str, indexed: bool) -> Result<(), Box<dyn Error>> {
+    let tree = MerkleTree::new(&read_list(list, indexed)?)?;
+    println!("{}", tree.hex_root());
+    Ok(())
+}
+
+fn proofs(list: &str, out_dir: &str, indexed: bool) -> Result<(), Box<dyn Error>> {
+    let accounts = read_list(list, indexed)?;
+    let tree = MerkleTree::new(&accounts)?;
+
+    fs::create_dir_all(out_dir)?;
+    for account in &accounts {
+        let proof = tree
+            .account_proof(account)
+            .expect("every listed account is in the tree");
+        let path = Path::new(out_dir).join(format!("{}.json", account.address));
+        fs::write(path, serde_json::to_string_pretty(&proof)?)?;
+    }
+    println!("{}", tree.hex_root());
+    Ok(())
+}